@@ -0,0 +1,796 @@
+use std::{
+    borrow::Cow,
+    error::Error,
+    io::{BufRead, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use quickemu_core::{
+    config::{Config, ParsedVM},
+    console::{ConsoleExit, ConsoleOptions},
+    data::{MonitorArg, MonitorInner},
+    host_network,
+    legacy::LegacyImport,
+    libvirt::LibvirtImport,
+    live_vm::{LiveVM, ShutdownMethod},
+    overrides::Overrides,
+    registry::VmRegistry,
+    systemd::{UnitOptions, UnitScope},
+    validate::Severity,
+};
+use size::Size;
+
+#[cfg(unix)]
+use quickemu_core::guest_agent::{GuestAgent, GuestShutdownMode};
+
+#[cfg(unix)]
+use crate::ShutdownModeArg;
+#[cfg(not(target_os = "macos"))]
+use crate::ViewerArg;
+
+const SUPERVISE_INTERVAL: Duration = Duration::from_millis(200);
+const SIGNAL_STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn running_vm(config_file: &Path) -> Result<(Config, LiveVM)> {
+    let config = Config::read(config_file)?;
+    let live_vm = config
+        .live_vm()?
+        .ok_or_else(|| format!("VM {} is not running", config.vm_name))?;
+    Ok((config, live_vm))
+}
+
+pub fn start(config_file: &Path, overrides: &Overrides, detach: bool, json: bool) -> Result<()> {
+    let config = match Config::parse_with_overrides(config_file, overrides).map_err(|e| format!("Couldn't parse config: {e}"))? {
+        ParsedVM::Config(config) => config,
+        ParsedVM::Live(live_vm) => return Err(format!("VM is already running (PID {})", live_vm.pid).into()),
+    };
+
+    let result = if detach { config.launch_detached()? } else { config.launch()? };
+
+    if json {
+        // Warnings are part of the report
+        println!("{}", result.report.to_json());
+    } else {
+        result.warnings.iter().for_each(|warning| log::warn!("{warning}"));
+        result
+            .display
+            .iter()
+            .for_each(|display| println!(" - {}: {}", display.name, display.value));
+    }
+
+    let mut supervisor = result.supervisor;
+    if detach {
+        supervisor.detach().iter().for_each(|err| log::warn!("{err}"));
+        return Ok(());
+    }
+
+    crate::signals::install();
+    let report = loop {
+        if let Some(report) = supervisor.try_wait()? {
+            break report;
+        }
+        if crate::signals::received() {
+            log::info!("Shutting down VM (PID {})", supervisor.pid());
+            if let Err(err) = supervisor.stop(SIGNAL_STOP_TIMEOUT) {
+                log::warn!("{err}");
+            }
+        }
+        std::thread::sleep(SUPERVISE_INTERVAL);
+    };
+
+    report.errors.iter().for_each(|err| log::warn!("{err}"));
+    report.warnings.iter().for_each(|warning| log::warn!("{warning}"));
+    let restarts = match report.restarts {
+        0 => String::new(),
+        1 => " after 1 restart".to_string(),
+        restarts => format!(" after {restarts} restarts"),
+    };
+    match report.success() {
+        true => Ok(()),
+        false => Err(format!("QEMU exited with {}{restarts}", report.status).into()),
+    }
+}
+
+pub fn stop(config_file: &Path, overrides: &Overrides, timeout: Duration, pid: Option<u32>) -> Result<()> {
+    let (config, live_vm) = match pid {
+        Some(pid) => {
+            let config = Config::read_with_overrides(config_file, overrides)?;
+            (config.clone(), config.attach(pid))
+        }
+        None => running_vm(config_file)?,
+    };
+    let method = live_vm.shutdown(timeout)?;
+    let how = match method {
+        ShutdownMethod::Powerdown => "shut down",
+        ShutdownMethod::Terminated => "terminated",
+        ShutdownMethod::Killed => "killed",
+    };
+    println!("VM {} was {how}", config.vm_name);
+    Ok(())
+}
+
+pub fn reboot(config_file: &Path) -> Result<()> {
+    let (config, live_vm) = running_vm(config_file)?;
+    live_vm.reboot()?;
+    println!("Requested reboot of VM {}", config.vm_name);
+    Ok(())
+}
+
+pub fn reset(config_file: &Path) -> Result<()> {
+    let (config, live_vm) = running_vm(config_file)?;
+    live_vm.reset()?;
+    println!("Reset VM {}", config.vm_name);
+    Ok(())
+}
+
+pub fn pause(config_file: &Path) -> Result<()> {
+    let (config, live_vm) = running_vm(config_file)?;
+    live_vm.pause()?;
+    println!("Paused VM {}", config.vm_name);
+    Ok(())
+}
+
+pub fn resume(config_file: &Path) -> Result<()> {
+    let (config, live_vm) = running_vm(config_file)?;
+    live_vm.resume()?;
+    println!("Resumed VM {}", config.vm_name);
+    Ok(())
+}
+
+pub fn kill(config_file: &Path) -> Result<()> {
+    let (config, live_vm) = running_vm(config_file)?;
+    live_vm.kill()?;
+    println!("Killed VM {} (PID {})", config.vm_name, live_vm.pid);
+    Ok(())
+}
+
+pub fn status(config_file: &Path) -> Result<()> {
+    let config = Config::read(config_file)?;
+    let Some(live_vm) = config.live_vm()? else {
+        println!("{}: not running", config.vm_name);
+        return Ok(());
+    };
+
+    println!("{}: running", config.vm_name);
+    println!(" - PID: {}", live_vm.pid);
+    if let Some(port) = live_vm.ssh_port {
+        println!(" - SSH (Host): ssh {{user}}@localhost -p {port}");
+    }
+    #[cfg(not(target_os = "macos"))]
+    if let Some(port) = live_vm.spice_port {
+        println!(" - Spice: localhost:{port}");
+    }
+    println!(" - Monitor: {}", monitor_display(&live_vm.monitor));
+    println!(" - Serial: {}", monitor_display(&live_vm.serial));
+    if let Some(socket) = &live_vm.guest_agent {
+        println!(" - Guest agent: {}", socket.display());
+    }
+    if let Some(log_file) = &live_vm.log_file {
+        println!(" - Log: {}", log_file.display());
+    }
+    if live_vm.restarts > 0 {
+        println!(" - Restarts: {}", live_vm.restarts);
+    }
+    Ok(())
+}
+
+pub fn list(dirs: Vec<PathBuf>) -> Result<()> {
+    let registry = match dirs.is_empty() {
+        true => VmRegistry::new([std::env::current_dir()?]),
+        false => VmRegistry::new(dirs),
+    };
+    let result = registry.scan();
+    result
+        .errors
+        .iter()
+        .for_each(|(path, error)| log::warn!("{}: {error}", path.display()));
+
+    let optional = |value: Option<u16>| value.map_or_else(|| "-".to_string(), |value| value.to_string());
+    let rows = result
+        .vms
+        .iter()
+        .map(|vm| {
+            [
+                vm.name.clone(),
+                vm.guest.to_string(),
+                vm.arch.to_string(),
+                vm.pid.map_or_else(|| "stopped".to_string(), |pid| pid.to_string()),
+                optional(vm.ssh_port),
+                #[cfg(not(target_os = "macos"))]
+                optional(vm.spice_port),
+                Size::from_bytes(vm.disk_usage).to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let header = [
+        "NAME",
+        "GUEST",
+        "ARCH",
+        "PID",
+        "SSH",
+        #[cfg(not(target_os = "macos"))]
+        "SPICE",
+        "DISK",
+    ];
+    let widths = header
+        .iter()
+        .enumerate()
+        .map(|(i, title)| rows.iter().map(|row| row[i].len()).chain([title.len()]).max().unwrap_or(0))
+        .collect::<Vec<_>>();
+    let print_row = |row: &[&str]| {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{value:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    };
+
+    print_row(&header);
+    for row in &rows {
+        print_row(&row.each_ref().map(String::as_str));
+    }
+    Ok(())
+}
+
+fn monitor_display<T: MonitorArg>(monitor: &MonitorInner<T>) -> Cow<'static, str> {
+    match monitor {
+        MonitorInner::None => Cow::Borrowed("None"),
+        MonitorInner::Telnet { address } => Cow::Owned(format!("telnet {}", address.as_ref())),
+        #[cfg(unix)]
+        MonitorInner::Socket { socketpath: Some(socketpath) } => Cow::Owned(format!("socket {}", socketpath.display())),
+        MonitorInner::Qmp { address: Some(address), .. } => Cow::Owned(format!("QMP tcp {}", address.as_ref())),
+        #[cfg(unix)]
+        MonitorInner::Qmp { socketpath: Some(socketpath), .. } => Cow::Owned(format!("QMP socket {}", socketpath.display())),
+        _ => Cow::Borrowed("Unknown"),
+    }
+}
+
+pub fn logs(config_file: &Path, follow: bool, lines: Option<usize>) -> Result<()> {
+    let config = Config::read(config_file)?;
+    let live_vm = config.live_vm()?;
+    let log_file = live_vm
+        .as_ref()
+        .and_then(|live_vm| live_vm.log_file.clone())
+        .unwrap_or_else(|| config.log_file());
+    let mut file = std::fs::File::open(&log_file).map_err(|e| format!("Could not open {}: {e}", log_file.display()))?;
+
+    let contents = std::fs::read(&log_file)?;
+    let start = match lines {
+        Some(lines) => contents
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, b)| **b == b'\n')
+            .nth(lines)
+            .map_or(0, |(i, _)| i + 1),
+        None => 0,
+    };
+    let mut stdout = std::io::stdout();
+    stdout.write_all(&contents[start..])?;
+    stdout.flush()?;
+
+    let Some(live_vm) = live_vm.filter(|_| follow) else {
+        return Ok(());
+    };
+    let mut position = file.seek(SeekFrom::Start(contents.len() as u64))?;
+    loop {
+        let active = live_vm.is_active();
+        // Start over if the log was truncated
+        if file.metadata()?.len() < position {
+            position = file.seek(SeekFrom::Start(0))?;
+        }
+        position += std::io::copy(&mut file, &mut stdout)?;
+        stdout.flush()?;
+        if !active {
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
+
+pub fn monitor(config_file: &Path, command: &str) -> Result<()> {
+    let (_, live_vm) = running_vm(config_file)?;
+    let response = live_vm.send_monitor_cmd(command)?;
+    println!("{}", response.trim_end());
+    Ok(())
+}
+
+pub fn console(config_file: &Path, log: Option<PathBuf>, no_log: bool, escape: char) -> Result<()> {
+    let (config, live_vm) = running_vm(config_file)?;
+    let options = ConsoleOptions {
+        // Ctrl clears the upper bits of the key pressed with it
+        escape: escape as u8 & 0x1f,
+        log_file: (!no_log).then(|| log.unwrap_or_else(|| config.console_log_file())),
+    };
+
+    eprintln!(
+        "Connected to the console of VM {}. Press Ctrl+{escape} to detach",
+        config.vm_name
+    );
+    let exit = live_vm.console(&options)?;
+    eprintln!();
+    match exit {
+        ConsoleExit::Detached => eprintln!("Detached from VM {}", config.vm_name),
+        ConsoleExit::Closed => eprintln!("The console of VM {} was closed", config.vm_name),
+    }
+    Ok(())
+}
+
+pub fn ssh(config_file: &Path, user: Option<&str>, args: &[String]) -> Result<()> {
+    let (config, live_vm) = running_vm(config_file)?;
+    let port = live_vm
+        .ssh_port
+        .ok_or_else(|| format!("VM {} does not have an SSH port forwarded", config.vm_name))?;
+    let host = match user {
+        Some(user) => format!("{user}@localhost"),
+        None => "localhost".to_string(),
+    };
+
+    let mut command = Command::new("ssh");
+    command.arg("-p").arg(port.to_string()).arg(host).args(args);
+    run_foreground(command, "ssh")
+}
+
+#[cfg(unix)]
+fn guest_agent(config_file: &Path) -> Result<(Config, GuestAgent)> {
+    let (config, live_vm) = running_vm(config_file)?;
+    let agent = live_vm.guest_agent()?;
+    Ok((config, agent))
+}
+
+#[cfg(unix)]
+pub fn agent_ping(config_file: &Path) -> Result<()> {
+    let (config, mut agent) = guest_agent(config_file)?;
+    agent.ping()?;
+    println!("The guest agent of VM {} is responding", config.vm_name);
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn agent_os_info(config_file: &Path) -> Result<()> {
+    let (_, mut agent) = guest_agent(config_file)?;
+    let info = agent.os_info()?;
+    let fields = [
+        ("Name", info.pretty_name.or(info.name)),
+        ("Version", info.version),
+        ("Kernel", info.kernel_release),
+        ("Architecture", info.machine),
+    ];
+    for (name, value) in fields {
+        if let Some(value) = value {
+            println!("{name}: {value}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn agent_interfaces(config_file: &Path) -> Result<()> {
+    let (_, mut agent) = guest_agent(config_file)?;
+    for interface in agent.network_interfaces()? {
+        match &interface.hardware_address {
+            Some(mac) => println!("{} ({mac})", interface.name),
+            None => println!("{}", interface.name),
+        }
+        for address in &interface.ip_addresses {
+            println!(" - {}/{}", address.ip_address, address.prefix);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn agent_exec(config_file: &Path, command: &[String]) -> Result<()> {
+    let (_, mut agent) = guest_agent(config_file)?;
+    let (path, args) = command.split_first().ok_or("No command provided")?;
+    // Commands may run for longer than the agent's usual response timeout
+    agent.set_timeout(None)?;
+    let output = agent.exec(path, args, None)?;
+
+    std::io::stdout().write_all(&output.stdout)?;
+    std::io::stderr().write_all(&output.stderr)?;
+    if output.truncated {
+        log::warn!("The output of {path} was truncated by the guest agent");
+    }
+    match (output.exit_code, output.signal) {
+        (Some(0), _) => Ok(()),
+        (Some(code), _) => std::process::exit(code),
+        (None, Some(signal)) => Err(format!("{path} was killed by signal {signal}").into()),
+        (None, None) => Err(format!("{path} exited without a status").into()),
+    }
+}
+
+#[cfg(unix)]
+pub fn agent_read(config_file: &Path, path: &str, output: Option<PathBuf>) -> Result<()> {
+    let (_, mut agent) = guest_agent(config_file)?;
+    let data = agent.read_file(path)?;
+    match output {
+        Some(output) => std::fs::write(&output, data).map_err(|e| format!("Could not write {}: {e}", output.display()))?,
+        None => std::io::stdout().write_all(&data)?,
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn agent_write(config_file: &Path, path: &str, input: Option<PathBuf>) -> Result<()> {
+    let data = match input {
+        Some(input) => std::fs::read(&input).map_err(|e| format!("Could not read {}: {e}", input.display()))?,
+        None => {
+            let mut data = Vec::new();
+            std::io::Read::read_to_end(&mut std::io::stdin(), &mut data)?;
+            data
+        }
+    };
+    let (config, mut agent) = guest_agent(config_file)?;
+    agent.write_file(path, &data)?;
+    println!("Wrote {} to {path} in VM {}", Size::from_bytes(data.len()), config.vm_name);
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn agent_shutdown(config_file: &Path, mode: ShutdownModeArg) -> Result<()> {
+    let (config, mut agent) = guest_agent(config_file)?;
+    let (mode, action) = match mode {
+        ShutdownModeArg::Powerdown => (GuestShutdownMode::Powerdown, "shut down"),
+        ShutdownModeArg::Halt => (GuestShutdownMode::Halt, "halt"),
+        ShutdownModeArg::Reboot => (GuestShutdownMode::Reboot, "reboot"),
+    };
+    agent.shutdown(mode)?;
+    println!("Asked the guest agent of VM {} to {action}", config.vm_name);
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn viewer(config_file: &Path, viewer: ViewerArg) -> Result<()> {
+    let (config, live_vm) = running_vm(config_file)?;
+    let port = live_vm
+        .spice_port
+        .ok_or_else(|| format!("VM {} does not have a SPICE display", config.vm_name))?;
+
+    let (bin, mut command) = match viewer {
+        ViewerArg::Spicy => ("spicy", Command::new("spicy")),
+        ViewerArg::Remote => ("remote-viewer", Command::new("remote-viewer")),
+    };
+    command.arg("--title").arg(&config.vm_name);
+    match viewer {
+        ViewerArg::Spicy => command.arg("--port").arg(port.to_string()),
+        ViewerArg::Remote => command.arg(format!("spice://localhost:{port}")),
+    };
+    command.spawn().map_err(|e| format!("Failed to launch {bin}: {e}"))?;
+    Ok(())
+}
+
+pub fn snapshot_create(config_file: &Path, name: &str) -> Result<()> {
+    let config = Config::read(config_file)?;
+    config.create_snapshot(name)?;
+    println!("Created snapshot {name} of VM {}", config.vm_name);
+    Ok(())
+}
+
+pub fn snapshot_list(config_file: &Path) -> Result<()> {
+    let config = Config::read(config_file)?;
+    let snapshots = config.snapshots()?;
+    if snapshots.is_empty() {
+        println!("VM {} has no snapshots", config.vm_name);
+        return Ok(());
+    }
+
+    for snapshot in snapshots {
+        let age = snapshot.date.elapsed().unwrap_or_default().as_secs();
+        let age = match age {
+            0..=59 => format!("{age}s ago"),
+            60..=3599 => format!("{}m ago", age / 60),
+            3600..=86399 => format!("{}h ago", age / 3600),
+            _ => format!("{}d ago", age / 86400),
+        };
+        let state = match snapshot.vm_state_size {
+            0 => "disk only",
+            _ => "with VM state",
+        };
+        println!(" - {}: {age} ({state})", snapshot.name);
+    }
+    Ok(())
+}
+
+pub fn snapshot_revert(config_file: &Path, name: &str) -> Result<()> {
+    let config = Config::read(config_file)?;
+    config.revert_snapshot(name)?;
+    println!("Reverted VM {} to snapshot {name}", config.vm_name);
+    Ok(())
+}
+
+pub fn snapshot_delete(config_file: &Path, name: &str) -> Result<()> {
+    let config = Config::read(config_file)?;
+    config.delete_snapshot(name)?;
+    println!("Deleted snapshot {name} of VM {}", config.vm_name);
+    Ok(())
+}
+
+pub fn import(source: &Path, output: Option<PathBuf>) -> Result<()> {
+    let output = output.unwrap_or_else(|| source.with_extension("toml"));
+    if output.exists() {
+        return Err(format!("{} already exists", output.display()).into());
+    }
+
+    let (config, unsupported) = if source.extension().is_some_and(|extension| extension == "xml") {
+        // libvirt domains reference their images by absolute paths, so the VM directory is left beside the new configuration
+        let LibvirtImport { config, unsupported } = Config::import_libvirt(source)?;
+        (config, unsupported.iter().map(ToString::to_string).collect::<Vec<_>>())
+    } else {
+        let LegacyImport { mut config, unsupported } = Config::import_legacy(source)?;
+        // The VM directory is only implied when the configuration sits alongside it with a matching name
+        let implied_vm_dir = std::path::absolute(source)?.with_extension("");
+        if config.vm_dir.is_none() && std::path::absolute(&output)?.with_extension("") != implied_vm_dir {
+            config.vm_dir = Some(implied_vm_dir);
+        }
+        (config, unsupported.iter().map(ToString::to_string).collect())
+    };
+
+    #[cfg(unix)]
+    let shebang = std::env::current_exe()
+        .map(|path| format!("#!{} --vm\n", path.display()))
+        .unwrap_or_default();
+    #[cfg(not(unix))]
+    let shebang = String::new();
+    std::fs::write(&output, shebang + &config.to_toml()?)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&output, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    println!("Wrote {}", output.display());
+    if !unsupported.is_empty() {
+        println!("The following settings could not be converted:");
+        for key in &unsupported {
+            println!("  {key}");
+        }
+    }
+    Ok(())
+}
+
+pub fn check(config_file: &Path, overrides: &Overrides) -> Result<()> {
+    let diagnostics = Config::check_with_overrides(config_file, overrides)?;
+
+    // Formatted like compiler diagnostics, so that editors can jump to each location
+    for diagnostic in &diagnostics {
+        let location = match diagnostic.span {
+            Some(span) => format!("{}:{}:{}", config_file.display(), span.start.line, span.start.column),
+            None => config_file.display().to_string(),
+        };
+        let severity = match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &diagnostic.key {
+            Some(key) => println!("{location}: {severity}: {} [{key}]", diagnostic.message),
+            None => println!("{location}: {severity}: {}", diagnostic.message),
+        }
+        if let Some(suggestion) = &diagnostic.suggestion {
+            println!("    help: {suggestion}");
+        }
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    if errors > 0 {
+        return Err(format!("Found {errors} error(s) in {}", config_file.display()).into());
+    }
+    Ok(())
+}
+
+pub fn show_config(config_file: &Path, overrides: &Overrides) -> Result<()> {
+    let config = Config::read_with_overrides(config_file, overrides)?;
+    print!("{}", config.to_toml()?);
+    Ok(())
+}
+
+pub fn args(config_file: &Path, overrides: &Overrides) -> Result<()> {
+    let plan = Config::read_with_overrides(config_file, overrides)?.plan()?;
+
+    plan.qemu_args.warnings.iter().for_each(|warning| log::warn!("{warning}"));
+    println!("{}", plan.command_line());
+    Ok(())
+}
+
+pub fn dry_run(config_file: &Path, overrides: &Overrides, json: bool) -> Result<()> {
+    let plan = Config::read_with_overrides(config_file, overrides)?.plan()?;
+
+    if json {
+        println!("{}", plan.to_json());
+        return Ok(());
+    }
+
+    plan.qemu_args.warnings.iter().for_each(|warning| log::warn!("{warning}"));
+    plan.qemu_args
+        .display
+        .iter()
+        .for_each(|display| println!(" - {}: {}", display.name, display.value));
+    if !plan.actions().is_empty() {
+        println!();
+        plan.actions().iter().for_each(|action| println!("{action}"));
+    }
+    println!();
+    println!("{}", plan.command_line());
+    Ok(())
+}
+
+pub fn systemd(config_file: &Path, overrides: Overrides, system: bool, install: bool, output: Option<PathBuf>, stop_timeout: Duration) -> Result<()> {
+    // The service runs from a different working directory, so every path it references must be absolute
+    let config_file = std::path::absolute(config_file)?;
+    let scope = if system { UnitScope::System } else { UnitScope::User };
+    let config = Config::read_with_overrides(&config_file, &overrides)?;
+    let options = UnitOptions {
+        overrides,
+        stop_timeout,
+        ..UnitOptions::new(scope, &std::env::current_exe()?, &config_file)
+    };
+    let unit = config.systemd_unit(&options)?;
+    unit.warnings.iter().for_each(|warning| log::warn!("{warning}"));
+
+    let output = match (install, output) {
+        (true, _) => Some(unit.install_path(scope).ok_or("Could not find the systemd unit directory")?),
+        (false, output) => output,
+    };
+    let Some(output) = output else {
+        print!("{}", unit.contents);
+        return Ok(());
+    };
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&output, &unit.contents)?;
+    println!("Wrote {}", output.display());
+    if install {
+        let systemctl = if system { "systemctl" } else { "systemctl --user" };
+        println!(
+            "Enable it with: {systemctl} daemon-reload && {systemctl} enable --now {}",
+            unit.name
+        );
+    }
+    Ok(())
+}
+
+pub fn export_libvirt(config_file: &Path, overrides: &Overrides, output: Option<PathBuf>) -> Result<()> {
+    let config = Config::read_with_overrides(config_file, overrides)?;
+    let xml = config.to_libvirt_xml()?;
+    match output {
+        Some(output) => {
+            std::fs::write(&output, xml)?;
+            println!("Wrote {}", output.display());
+        }
+        None => print!("{xml}"),
+    }
+    Ok(())
+}
+
+pub fn network_setup(config_file: &Path, overrides: &Overrides, print: bool) -> Result<()> {
+    let config = Config::read_with_overrides(config_file, overrides)?;
+    let commands = config.host_network_setup();
+    if commands.is_empty() {
+        println!("The network devices are already set up");
+    } else if print || !host_network::is_privileged() {
+        if !print {
+            println!("Run the following commands as root to set up the network devices:");
+        }
+        commands.iter().for_each(|command| println!("{command}"));
+    } else {
+        for command in commands {
+            println!("{command}");
+            command.run()?;
+        }
+    }
+    Ok(())
+}
+
+pub fn edit(config_file: &Path) -> Result<()> {
+    let editor = std::env::var_os("VISUAL")
+        .or_else(|| std::env::var_os("EDITOR"))
+        .unwrap_or_else(|| "vi".into());
+
+    let mut command = Command::new(&editor);
+    command.arg(config_file);
+    let status = command
+        .status()
+        .map_err(|e| format!("Failed to launch editor {}: {e}", editor.to_string_lossy()))?;
+    if !status.success() {
+        return Err(format!("Editor exited with {status}").into());
+    }
+
+    Config::read(config_file).map_err(|e| format!("The edited configuration is invalid: {e}"))?;
+    Ok(())
+}
+
+pub fn delete(config_file: &Path, yes: bool, keep_config: bool) -> Result<()> {
+    let config = Config::read(config_file)?;
+    if let Some(live_vm) = config.live_vm()? {
+        return Err(format!(
+            "VM {} is running (PID {}). Stop it before deleting.",
+            config.vm_name, live_vm.pid
+        )
+        .into());
+    }
+    let vm_dir = config.vm_dir.as_ref().expect("VM directory should be set");
+    let owns_dir = owns_vm_dir(config_file, &config, vm_dir);
+    let vm_files = match owns_dir {
+        true => Vec::new(),
+        false => config.vm_files().into_iter().filter(|file| file.exists()).collect(),
+    };
+
+    if !yes {
+        match owns_dir {
+            true => print!("Delete {}", vm_dir.display()),
+            false => {
+                println!(
+                    "{} may be shared with other VMs, so only files belonging to this VM are deleted:",
+                    vm_dir.display()
+                );
+                vm_files.iter().for_each(|file| println!(" - {}", file.display()));
+                print!("Delete these files");
+            }
+        }
+        if !keep_config {
+            print!(" and {}", config_file.display());
+        }
+        print!("? [y/N] ");
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        std::io::stdin().lock().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            return Ok(());
+        }
+    }
+
+    if owns_dir && vm_dir.exists() {
+        std::fs::remove_dir_all(vm_dir).map_err(|e| format!("Could not delete {}: {e}", vm_dir.display()))?;
+    }
+    for file in vm_files {
+        std::fs::remove_file(&file).map_err(|e| format!("Could not delete {}: {e}", file.display()))?;
+    }
+    if !keep_config {
+        std::fs::remove_file(config_file).map_err(|e| format!("Could not delete {}: {e}", config_file.display()))?;
+    }
+    Ok(())
+}
+
+// The VM directory is only deleted as a whole when it's the one named after the VM beside its config, and holds no other configs.
+// Otherwise it may have been set by defaults or an include, and be shared with other VMs
+fn owns_vm_dir(config_file: &Path, config: &Config, vm_dir: &Path) -> bool {
+    let vm_name = match config.vm_name.is_empty() {
+        true => config_file.file_stem().unwrap_or_default().to_string_lossy(),
+        false => Cow::Borrowed(config.vm_name.as_str()),
+    };
+    let own_dir = config_file.parent().unwrap_or(Path::new("")).join(vm_name.as_ref());
+    let same_dir = own_dir
+        .canonicalize()
+        .ok()
+        .zip(vm_dir.canonicalize().ok())
+        .is_some_and(|(a, b)| a == b);
+    let other_configs = std::fs::read_dir(vm_dir).is_ok_and(|entries| {
+        entries
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.path().extension().is_some_and(|ext| ext == "toml"))
+    });
+    same_dir && !other_configs
+}
+
+fn run_foreground(mut command: Command, bin: &str) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let err = command.exec();
+        Err(format!("Failed to launch {bin}: {err}").into())
+    }
+    #[cfg(not(unix))]
+    {
+        let status = command.status().map_err(|e| format!("Failed to launch {bin}: {e}"))?;
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
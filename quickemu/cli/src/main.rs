@@ -1,26 +1,323 @@
-use std::{error::Error, path::Path};
+mod actions;
+mod signals;
 
-use quickemu_core::config::{Config, ParsedVM};
-fn main() -> Result<(), Box<dyn Error>> {
-    env_logger::builder().filter_level(log::LevelFilter::Warn).init();
+use std::{error::Error, path::PathBuf, time::Duration};
 
-    let config_file = std::env::args().nth(1).ok_or("No config file provided")?;
-    let config = Config::parse(Path::new(&config_file)).map_err(|e| format!("Couldn't parse config: {e}"))?;
+use clap::{Parser, Subcommand};
+use quickemu_core::overrides::{Override, Overrides};
 
-    let result = match config {
-        ParsedVM::Config(config) => config.launch()?,
-        ParsedVM::Live(_) => return Err("VM is already running".into()),
-    };
+fn main() {
+    let args = Args::parse();
+    env_logger::builder().filter_level(args.verbose.log_level_filter()).init();
 
-    result.warnings.iter().for_each(|warning| log::warn!("{warning}"));
-    result
-        .display
-        .iter()
-        .for_each(|display| println!(" - {}: {}", display.name, display.value));
+    if let Err(e) = run(args) {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
 
-    for thread in result.threads {
-        thread.join().expect("Couldn't join thread")?;
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let action = args.action.unwrap_or(Action::Start {
+        dry_run: false,
+        json: false,
+        detach: false,
+    });
+    match action {
+        Action::List { dirs } => return actions::list(dirs),
+        Action::Import { source, output } => return actions::import(&source, output),
+        _ => {}
     }
+    let config_file = args.vm.or(args.config).ok_or("No config file provided")?;
+    // Overrides passed on the command line take precedence over the environment
+    let mut overrides = Overrides::from_env();
+    overrides.extend(args.set);
+
+    match action {
+        Action::Start { dry_run: true, json, .. } => actions::dry_run(&config_file, &overrides, json),
+        Action::Start { detach, json, .. } => actions::start(&config_file, &overrides, detach, json),
+        Action::Stop { timeout, pid } => actions::stop(&config_file, &overrides, Duration::from_secs(timeout), pid),
+        Action::Kill => actions::kill(&config_file),
+        Action::Reboot => actions::reboot(&config_file),
+        Action::Reset => actions::reset(&config_file),
+        Action::Pause => actions::pause(&config_file),
+        Action::Resume => actions::resume(&config_file),
+        Action::Status => actions::status(&config_file),
+        Action::Logs { follow, lines } => actions::logs(&config_file, follow, lines),
+        Action::Monitor { command } => actions::monitor(&config_file, &command.join(" ")),
+        Action::Console { log, no_log, escape } => actions::console(&config_file, log, no_log, escape),
+        Action::Ssh { user, args } => actions::ssh(&config_file, user.as_deref(), &args),
+        #[cfg(unix)]
+        Action::Agent { action } => match action {
+            AgentAction::Ping => actions::agent_ping(&config_file),
+            AgentAction::OsInfo => actions::agent_os_info(&config_file),
+            AgentAction::Interfaces => actions::agent_interfaces(&config_file),
+            AgentAction::Exec { command } => actions::agent_exec(&config_file, &command),
+            AgentAction::Read { path, output } => actions::agent_read(&config_file, &path, output),
+            AgentAction::Write { path, input } => actions::agent_write(&config_file, &path, input),
+            AgentAction::Shutdown { mode } => actions::agent_shutdown(&config_file, mode),
+        },
+        #[cfg(not(target_os = "macos"))]
+        Action::Viewer { viewer } => actions::viewer(&config_file, viewer),
+        Action::Snapshot { action } => match action {
+            SnapshotAction::Create { name } => actions::snapshot_create(&config_file, &name),
+            SnapshotAction::List => actions::snapshot_list(&config_file),
+            SnapshotAction::Revert { name } => actions::snapshot_revert(&config_file, &name),
+            SnapshotAction::Delete { name } => actions::snapshot_delete(&config_file, &name),
+        },
+        Action::Check => actions::check(&config_file, &overrides),
+        Action::ShowConfig => actions::show_config(&config_file, &overrides),
+        Action::Args => actions::args(&config_file, &overrides),
+        Action::List { .. } | Action::Import { .. } => unreachable!(),
+        Action::Systemd {
+            system,
+            install,
+            output,
+            stop_timeout,
+        } => actions::systemd(
+            &config_file,
+            overrides,
+            system,
+            install,
+            output,
+            Duration::from_secs(stop_timeout),
+        ),
+        Action::ExportLibvirt { output } => actions::export_libvirt(&config_file, &overrides, output),
+        Action::NetworkSetup { print } => actions::network_setup(&config_file, &overrides, print),
+        Action::Edit => actions::edit(&config_file),
+        Action::Delete { yes, keep_config } => actions::delete(&config_file, yes, keep_config),
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    #[command(flatten)]
+    verbose: clap_verbosity_flag::Verbosity<clap_verbosity_flag::WarnLevel>,
+    /// Path to the VM's configuration file
+    #[arg(long, global = true)]
+    vm: Option<PathBuf>,
+    /// Path to the VM's configuration file
+    #[arg(conflicts_with = "vm")]
+    config: Option<PathBuf>,
+    /// Override a configuration setting, e.g. `--set machine.ram=8G`. Can be repeated
+    #[arg(long, global = true, value_name = "KEY=VALUE")]
+    set: Vec<Override>,
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Action {
+    /// Launch the VM (default)
+    Start {
+        /// Print the QEMU command line and the actions launching would take, without launching
+        #[arg(long)]
+        dry_run: bool,
+        /// Print the launch report, or the dry run, as JSON instead of text
+        #[arg(long)]
+        json: bool,
+        /// Run the VM in the background, writing QEMU's output to a log file in the VM directory
+        #[arg(short, long, alias = "daemon", conflicts_with = "dry_run")]
+        detach: bool,
+    },
+    /// Shut down the VM through ACPI, terminating it if it does not exit in time
+    Stop {
+        /// Seconds to wait for the guest to shut down
+        #[arg(short, long, default_value_t = 60)]
+        timeout: u64,
+        /// Stop a VM which was started outside of quickemu with this PID, e.g. by a systemd service
+        #[arg(long)]
+        pid: Option<u32>,
+    },
+    /// Forcefully kill the running VM
+    Kill,
+    /// Request the guest to reboot (Ctrl+Alt+Delete)
+    Reboot,
+    /// Hard reset the VM
+    Reset,
+    /// Pause execution of the VM
+    Pause,
+    /// Resume execution of a paused VM
+    Resume,
+    /// Show whether the VM is running and how to connect to it
+    Status,
+    /// Print the log of a VM launched with `start --detach`
+    Logs {
+        /// Keep printing output as it is written, until the VM exits
+        #[arg(short, long)]
+        follow: bool,
+        /// Only print the last N lines
+        #[arg(short = 'n', long)]
+        lines: Option<usize>,
+    },
+    /// Send a command to the QEMU monitor of the running VM
+    Monitor {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Attach the terminal to the running VM's serial console. Press Ctrl+] (or the --escape key with Ctrl) to detach
+    #[command(alias = "attach")]
+    Console {
+        /// Append console output to this file (default: <vm_name>-console.log in the VM directory)
+        #[arg(long, conflicts_with = "no_log")]
+        log: Option<PathBuf>,
+        /// Do not log console output
+        #[arg(long)]
+        no_log: bool,
+        /// Detach when this key is pressed along with Ctrl
+        #[arg(long, default_value_t = ']', value_parser = parse_escape)]
+        escape: char,
+    },
+    /// Connect to the running VM through SSH
+    Ssh {
+        #[arg(short, long)]
+        user: Option<String>,
+        /// Extra arguments passed to ssh
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Query or control the guest through the QEMU guest agent. Requires `guest_agent = true` and qemu-ga running in the guest
+    #[cfg(unix)]
+    Agent {
+        #[command(subcommand)]
+        action: AgentAction,
+    },
+    /// Open a SPICE viewer for the running VM
+    #[cfg(not(target_os = "macos"))]
+    Viewer {
+        #[arg(long, value_enum, default_value_t)]
+        viewer: ViewerArg,
+    },
+    /// Manage snapshots of the VM's disks and state
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// List VMs in the given directories and whether they are running
+    List {
+        /// Directories to search for VM configuration files (default: current directory)
+        dirs: Vec<PathBuf>,
+    },
+    /// Convert a configuration file from the original bash quickemu, or a libvirt domain XML file, reporting settings which couldn't be converted
+    Import {
+        /// A quickemu .conf file, or a libvirt domain ending in .xml
+        source: PathBuf,
+        /// Where to write the converted configuration (default: alongside the original, with a .toml extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check the configuration file for problems without launching the VM
+    Check,
+    /// Print the effective configuration, after merging user defaults and included files
+    ShowConfig,
+    /// Print the QEMU command line used to launch the VM
+    Args,
+    /// Generate a systemd service which runs the VM, printing it unless --install or --output is given
+    Systemd {
+        /// Generate a system service rather than a user service
+        #[arg(long)]
+        system: bool,
+        /// Write the service to the systemd directory for its scope
+        #[arg(long, conflicts_with = "output")]
+        install: bool,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Seconds the guest is given to shut down when the service is stopped
+        #[arg(long, default_value_t = 60)]
+        stop_timeout: u64,
+    },
+    /// Export the VM as a libvirt domain XML file, printing it unless --output is given
+    ExportLibvirt {
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Create the bridges and TAP devices the VM's network interfaces use. Without root, the required commands are printed instead
+    NetworkSetup {
+        /// Only print the commands, even when running as root
+        #[arg(long)]
+        print: bool,
+    },
+    /// Open the configuration file in your editor
+    Edit,
+    /// Delete the VM directory and configuration file
+    Delete {
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// Only delete the VM directory
+        #[arg(long)]
+        keep_config: bool,
+    },
+}
 
-    Ok(())
+#[derive(Debug, Subcommand)]
+enum SnapshotAction {
+    /// Take a snapshot. Running VMs also have their memory and device state saved
+    Create { name: String },
+    /// List snapshots present on all of the VM's disks
+    List,
+    /// Revert the VM to a snapshot
+    Revert { name: String },
+    /// Delete a snapshot
+    Delete { name: String },
+}
+
+#[cfg(unix)]
+#[derive(Debug, Subcommand)]
+enum AgentAction {
+    /// Check that the guest agent is responding
+    Ping,
+    /// Show the guest's operating system
+    OsInfo,
+    /// List the guest's network interfaces and their IP addresses
+    #[command(alias = "ip")]
+    Interfaces,
+    /// Run a program in the guest, printing its output and exiting with its exit code
+    Exec {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Copy a file out of the guest, printing it unless --output is given
+    Read {
+        path: String,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Copy a file into the guest, reading it from standard input unless --input is given
+    Write {
+        path: String,
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
+    /// Shut down the guest from within, which works even when it ignores ACPI
+    Shutdown {
+        #[arg(long, value_enum, default_value_t)]
+        mode: ShutdownModeArg,
+    },
+}
+
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+enum ShutdownModeArg {
+    #[default]
+    Powerdown,
+    Halt,
+    Reboot,
+}
+
+#[cfg(not(target_os = "macos"))]
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+enum ViewerArg {
+    #[default]
+    Spicy,
+    Remote,
+}
+
+// Control characters are typed as Ctrl plus one of `@`, `A`-`Z`, `[`, `\\`, `]`, `^` or `_`
+fn parse_escape(value: &str) -> Result<char, String> {
+    let mut chars = value.strip_prefix('^').unwrap_or(value).chars();
+    match (chars.next().map(|c| c.to_ascii_uppercase()), chars.next()) {
+        (Some(c @ '@'..='_'), None) => Ok(c),
+        _ => Err("expected a single letter or one of @[\\]^_".to_string()),
+    }
 }
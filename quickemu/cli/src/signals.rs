@@ -0,0 +1,22 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static RECEIVED: AtomicBool = AtomicBool::new(false);
+
+// Catch SIGINT and SIGTERM, so that a running VM can be shut down cleanly rather than the CLI exiting under it
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        let handler = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+pub fn received() -> bool {
+    RECEIVED.swap(false, Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+extern "C" fn handle(_: libc::c_int) {
+    RECEIVED.store(true, Ordering::SeqCst);
+}
@@ -1,16 +1,50 @@
 # Config errors
 read-config-error = Could not read config file: { $err }
 parse-config-error = Could not parse config file: { $err }
+unsupported-config-version = Configuration version { $version } is not supported. This version of quickemu supports configurations up to version { $current }
+include-config-error = Could not include { $path }: { $err }
+include-cycle = { $path } includes itself
+invalid-override = Could not apply override { $key }. Overrides must be written as key=value, where key is a dotted path to a setting
+libvirt-config-error = Could not read libvirt domain: { $err }
 
 # Live VM errors
 failed-live-vm-de = Failed to deserialize live VM data: { $err }
 failed-del-live-file = Failed to delete inactive live VM status file: { $err }
 failed-vm-kill = Failed to kill running VM: { $err }
+vm-still-running = VM process { $pid } is still running after being killed.
 
 # Monitor errors
 no-monitor-available = No monitor is enabled.
 failed-monitor-write = Could not write to the monitor: { $err }
 failed-monitor-read = Could not read from thet monitor: { $err }
+no-qmp-available = The monitor is not configured to use QMP.
+failed-qmp-handshake = Received an invalid QMP greeting: { $greeting }
+failed-qmp-deserialize = Could not (de)serialize QMP message: { $err }
+qmp-command-error = QMP command failed ({ $class }): { $desc }
+qmp-timeout = Timed out waiting for a response from QMP.
+qmp-closed = The QMP connection was closed.
+
+# Console errors
+no-serial-available = The VM's serial port is not a socket or telnet endpoint, so its console can't be attached to.
+failed-serial-connect = Could not connect to the serial port: { $err }
+failed-raw-terminal = Could not switch the terminal to raw mode: { $err }
+console-io-error = Console connection failed: { $err }
+
+# Guest agent errors
+no-guest-agent = The guest agent is not enabled for this VM. Set `guest_agent = true` in its configuration and restart it.
+failed-guest-agent-connect = Could not connect to the guest agent socket: { $err }
+guest-agent-io-error = Guest agent connection failed: { $err }
+failed-guest-agent-deserialize = Could not (de)serialize guest agent message: { $err }
+guest-agent-command-error = Guest agent command failed ({ $class }): { $desc }
+guest-agent-timeout = Timed out waiting for the guest agent. Make sure qemu-ga is installed and running in the guest.
+guest-agent-closed = The guest agent connection was closed.
+
+# Snapshot errors
+snapshot-monitor-failed = Snapshot command failed: { $err }
+snapshot-qemu-img-failed = qemu-img snapshot failed: { $err }
+invalid-snapshot-name = Invalid snapshot name "{ $name }". Names must not be empty or contain whitespace.
+snapshot-unsupported-format = Disk { $disk } does not support snapshots. Only qcow2 disks can be snapshotted.
+snapshot-no-disks = The VM has no disks to snapshot.
 
 # Generic Errors
 macos-cpu-instructions = CPU does not support a necessary instruction for this macOS release: { $instruction }.
@@ -33,9 +67,55 @@ no-mac-bootloader = Could not find macOS bootloader in VM directory
 nonexistent-image = Requested to mount image { $img }, but it does not exist.
 monitor-command-failed = Could not send command to monitor: { $err }
 failed-live-vm-se = Failed to serialize live VM data: { $err }
+unsupported-qmp = { $monitor } does not support QMP.
+log-file-error = Could not open log file { $path }: { $err }
+supervise-error = Could not wait for QEMU: { $err }
+unit-missing-disk = Disk { $disk } does not exist yet. Launch the VM with quickemu once to create it before generating a service
+pre-start-hook-failed = Pre-start hook `{ $command }` failed, so the VM was not started: { $err }
+invalid-network-name = Private network name `{ $name }` is invalid. Names may only contain letters, numbers, '-', '_' and '.'
+runtime-dir-error = Could not create runtime directory { $dir }: { $err }
+stale-socket-error = Could not remove stale socket { $path }: { $err }
+missing-bridge = Network bridge { $bridge } does not exist.
+missing-tap = TAP device { $tap } does not exist.
+network-setup-failed = `{ $command }` failed: { $err }
+port-forward-range = Host ports { $host } cannot be forwarded to guest ports { $guest }. The guest must have a single port, or as many ports as the host.
+port-forward-conflict = Host { $protocol } port { $port } is forwarded more than once.
+nat-address = Address { $addr } is outside of the NAT network { $network }.
 
 # Warnings
 macos-core-power-two = macOS guests may not boot witwh core counts that are not powers of two. Recommended rounding: { $recommended }.
 software-virt-fallback = Hardware virtualization{ $virt_branding } is not enabled on your CPU. Falling back to software virtualization, performance will be degraded
 audio-backend-unavailable = Sound was requested, but no audio backend could be detected.
 insufficient-ram-configuration = The specified amount of RAM ({ $ram }) is insufficient for { $guest }. Performance issues may arise
+detached-restart = The restart policy only applies while quickemu is supervising the VM, and is ignored when it is launched detached
+unit-display = The { $display } display needs a graphical session, which services usually do not have. Consider setting io.display.type to "none" or "spice"
+unit-no-monitor = No monitor is configured, so the service can only terminate the VM rather than shut it down gracefully
+unit-hooks = Hooks are not run by services. Add them to the unit as ExecStartPre, ExecStartPost or ExecStopPost commands instead
+detached-hooks = Post-stop hooks only run while quickemu is supervising the VM, and are skipped when it is launched detached
+hook-output = { $stage } hook `{ $command }`: { $output }
+hook-failed = { $stage } hook `{ $command }` failed: { $err }
+
+# Dry run
+plan-create-disk = Would create { $format } disk { $path } of { $size }
+plan-copy-ovmf-vars = Would copy OVMF_VARS from { $from } to { $to }
+plan-start-swtpm = Would start { $binary } with socket { $socket }
+plan-launch-viewer = Would launch { $viewer } on port { $port }
+plan-send-monitor-commands = Would send monitor commands: { $commands }
+
+# Validation suggestions
+suggest-install-qemu = Install QEMU, or check that it is in your PATH
+suggest-install-swtpm = Install swtpm, or set machine.tpm = false
+suggest-efi-boot = Use EFI boot: machine.boot = {"{"} type = "efi" {"}"}
+suggest-install-ovmf = Install OVMF (edk2) firmware
+suggest-riscv64-bootloader = Install U-Boot for qemu-riscv64, or place a bootloader in the VM directory
+suggest-mac-bootloader = Download the macOS bootloader with quickget
+suggest-xhci = Set io.usb_controller = "xhci", or choose a different sound card
+suggest-other-port = Choose a different port, or stop the process using it
+suggest-qmp-monitor = Use QMP for the monitor instead of the serial port
+suggest-image-path = Check that the path is correct, or remove the image
+suggest-set-ram = Set machine.ram to at least 4G
+suggest-viewer = Install the viewer, or set io.display.viewer = "none"
+suggest-network-setup = Create it with `quickemu-rs network-setup`, which lists the commands required
+suggest-nat-address = Choose addresses within the network, or widen its prefix
+suggest-network-name = Rename the network, e.g. "cluster-1"
+suggest-cpu-threads = Set machine.cpu_threads = { $recommended }
@@ -1,6 +1,11 @@
 mod arch;
 mod guest;
+mod guest_agent;
 mod images;
 mod io;
 mod machine;
 mod network;
+
+pub(crate) use guest_agent::{GuestAgentArgs, GUEST_AGENT_PORT};
+pub(crate) use images::DriveBus;
+pub(crate) use network::private_multicast_addr;
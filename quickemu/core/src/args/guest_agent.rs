@@ -0,0 +1,48 @@
+use std::{
+    borrow::Cow,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    arg, oarg,
+    utils::{ArgDisplay, EmulatorArgs, QemuArg},
+};
+
+// The name qemu-ga looks for among the guest's virtio-serial ports
+pub(crate) const GUEST_AGENT_PORT: &str = "org.qemu.guest_agent.0";
+
+// Holds the socket QEMU listens on for the host side of the channel, when the guest agent is enabled
+pub(crate) struct GuestAgentArgs {
+    socket: Option<PathBuf>,
+}
+
+impl GuestAgentArgs {
+    pub(crate) fn new(socket: Option<&Path>) -> Self {
+        Self {
+            socket: socket.map(Path::to_path_buf),
+        }
+    }
+}
+
+impl EmulatorArgs for GuestAgentArgs {
+    fn display(&self) -> impl IntoIterator<Item = ArgDisplay> {
+        self.socket.as_ref().map(|socket| ArgDisplay {
+            name: Cow::Borrowed("Guest agent"),
+            value: Cow::Owned(socket.display().to_string()),
+        })
+    }
+    fn qemu_args(&self) -> impl IntoIterator<Item = QemuArg> {
+        let Some(socket) = &self.socket else { return Vec::new() };
+        let mut chardev = OsString::from("socket,id=qga0,server=on,wait=off,path=");
+        chardev.push(socket);
+        vec![
+            arg!("-device"),
+            arg!("virtio-serial-pci,id=qga-serial"),
+            arg!("-chardev"),
+            oarg!(chardev),
+            arg!("-device"),
+            oarg!(format!("virtserialport,bus=qga-serial.0,chardev=qga0,name={GUEST_AGENT_PORT}")),
+        ]
+    }
+}
@@ -8,6 +8,7 @@ use itertools::chain;
 use crate::{
     data::{GuestOS, Images, Monitor},
     error::{Error, Warning},
+    plan::PlannedAction,
     utils::{ArgDisplay, EmulatorArgs, LaunchFn, LaunchFnReturn, QemuArg},
 };
 
@@ -16,9 +17,9 @@ mod img;
 mod iso;
 
 impl Images {
-    pub(crate) fn args(&self, guest: GuestOS, vm_dir: &Path, status_quo: bool, monitor: Monitor) -> Result<(ImageArgs, Option<Warning>), Error> {
+    pub(crate) fn args(&self, guest: GuestOS, vm_dir: &Path, status_quo: bool, monitor: Monitor, dry_run: bool) -> Result<(ImageArgs<'_>, Option<Warning>), Error> {
         let mut used_indices = HashSet::new();
-        let disks = self.disk_args(guest, vm_dir, status_quo, &mut used_indices)?;
+        let disks = self.disk_args(guest, vm_dir, status_quo, &mut used_indices, dry_run)?;
         let isos = self.iso_args(disks.installed(), guest, vm_dir, &mut used_indices)?;
         let imgs = self.img_args(disks.installed(), vm_dir, guest)?;
 
@@ -36,6 +37,24 @@ impl Images {
     }
 }
 
+// A drive attached to the VM, described for other tools such as libvirt
+pub(crate) struct Drive<'a> {
+    pub(crate) path: &'a Path,
+    pub(crate) cdrom: bool,
+    pub(crate) format: &'a str,
+    pub(crate) bus: DriveBus,
+    pub(crate) transient: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum DriveBus {
+    Virtio,
+    Sata,
+    Ide,
+    // Whichever interface QEMU gives drives on the machine type by default
+    Default,
+}
+
 pub(crate) struct ImageArgs<'a> {
     disks: DiskArgs<'a>,
     isos: IsoArgs<'a>,
@@ -43,6 +62,12 @@ pub(crate) struct ImageArgs<'a> {
     monitor_cmds: Option<MonitorCmds>,
 }
 
+impl ImageArgs<'_> {
+    pub(crate) fn drives(&self) -> Vec<Drive<'_>> {
+        chain!(self.disks.drives(), self.isos.drives(), self.imgs.drives()).collect()
+    }
+}
+
 impl EmulatorArgs for ImageArgs<'_> {
     fn display(&self) -> impl IntoIterator<Item = ArgDisplay> {
         chain!(
@@ -68,6 +93,16 @@ impl EmulatorArgs for ImageArgs<'_> {
             self.monitor_cmds.map(|cmds| cmds.launch_fns()).into_iter().flatten()
         )
     }
+    fn planned_actions(&self) -> impl IntoIterator<Item = PlannedAction> {
+        chain!(
+            self.disks.planned_actions(),
+            self.monitor_cmds
+                .as_ref()
+                .map(|cmds| cmds.planned_actions())
+                .into_iter()
+                .flatten(),
+        )
+    }
 }
 
 struct MonitorCmds {
@@ -99,4 +134,9 @@ impl EmulatorArgs for MonitorCmds {
 
         Some(LaunchFn::After(launch_fn))
     }
+    fn planned_actions(&self) -> impl IntoIterator<Item = PlannedAction> {
+        Some(PlannedAction::SendMonitorCommands {
+            commands: self.cmds.iter().map(|cmd| cmd.command.to_string()).collect(),
+        })
+    }
 }
@@ -8,14 +8,17 @@ use crate::{
     data::{DiskFormat, GuestOS, Images, MacOSRelease, PreAlloc},
     error::Error,
     oarg,
+    plan::PlannedAction,
     utils::{ArgDisplay, EmulatorArgs, QemuArg},
 };
 
+use super::{Drive, DriveBus};
+
 const MIN_DISK_SIZE: u64 = 197_632 * 8;
 const MAC_BOOTLOADER: [&str; 2] = ["OpenCore.qcow2", "ESP.qcow2"];
 
 impl<'a> Images {
-    pub(crate) fn disk_args(&'a self, guest: GuestOS, vm_dir: &Path, status_quo: bool, used_indices: &mut HashSet<u32>) -> Result<DiskArgs<'a>, Error> {
+    pub(crate) fn disk_args(&'a self, guest: GuestOS, vm_dir: &Path, status_quo: bool, used_indices: &mut HashSet<u32>, dry_run: bool) -> Result<DiskArgs<'a>, Error> {
         let mut key = 1;
 
         let non_disk_keys = match guest {
@@ -59,10 +62,12 @@ impl<'a> Images {
                 };
                 Ok(if !path.exists() {
                     let size = disk.size.unwrap_or(guest.default_disk_size());
-                    create_disk_image(&path, size, disk.format)?;
+                    if !dry_run {
+                        create_disk_image(&path, size, disk.format)?;
+                    }
                     MountedDisk::new(path, disk.format, &mut key, used_indices, true, size)
                 } else {
-                    let QemuImgInfo { actual_size, virtual_size } = find_disk_size(&path)?;
+                    let QemuImgInfo { actual_size, virtual_size } = find_disk_size(&path, dry_run)?;
                     if disk.format.prealloc_enabled() || actual_size >= MIN_DISK_SIZE {
                         installed = true;
                     }
@@ -82,6 +87,7 @@ impl<'a> Images {
             ahci,
             bootloader,
             installed,
+            dry_run,
         })
     }
 }
@@ -115,11 +121,16 @@ struct QemuImgInfo {
     virtual_size: u64,
 }
 
-fn find_disk_size(path: &Path) -> Result<QemuImgInfo, Error> {
+// Without `force_share`, a disk which is locked by a running QEMU is reported as in use.
+// Dry runs and validation only read the disk's size, so they share the lock instead
+fn find_disk_size(path: &Path, force_share: bool) -> Result<QemuImgInfo, Error> {
     #[cfg(not(feature = "inbuilt_commands"))]
     let mut command = Command::new("qemu-img");
 
     command.arg("info").arg(path).arg("--output=json");
+    if force_share {
+        command.arg("--force-share");
+    }
 
     let output = command.output().map_err(|e| Error::Command("qemu-img", e.to_string()))?;
 
@@ -137,12 +148,28 @@ pub(crate) struct DiskArgs<'a> {
     ahci: bool,
     bootloader: Option<MountedDisk<'a>>,
     installed: bool,
+    dry_run: bool,
 }
 
 impl DiskArgs<'_> {
     pub(crate) fn installed(&self) -> bool {
         self.installed
     }
+    pub(crate) fn drives(&self) -> impl Iterator<Item = Drive<'_>> {
+        let bus = match self.guest {
+            GuestOS::MacOS { release } if release < MacOSRelease::Catalina => DriveBus::Sata,
+            GuestOS::KolibriOS => DriveBus::Sata,
+            GuestOS::ReactOS => DriveBus::Ide,
+            _ => DriveBus::Virtio,
+        };
+        self.bootloader.iter().chain(&self.mounted_disks).map(move |disk| Drive {
+            path: &disk.path,
+            cdrom: false,
+            format: disk.format.as_ref(),
+            bus,
+            transient: self.status_quo,
+        })
+    }
 }
 
 impl EmulatorArgs for DiskArgs<'_> {
@@ -169,6 +196,16 @@ impl EmulatorArgs for DiskArgs<'_> {
             args
         }))
     }
+    fn planned_actions(&self) -> impl IntoIterator<Item = PlannedAction> {
+        self.mounted_disks
+            .iter()
+            .filter(|disk| self.dry_run && disk.is_new)
+            .map(|disk| PlannedAction::CreateDisk {
+                path: disk.path.to_path_buf(),
+                size: disk.size,
+                format: disk.format.as_ref().to_string(),
+            })
+    }
 }
 
 struct MountedDisk<'a> {
@@ -8,6 +8,8 @@ use crate::{
     utils::{ArgDisplay, EmulatorArgs, QemuArg},
 };
 
+use super::{Drive, DriveBus};
+
 impl<'a> Images {
     pub(crate) fn img_args(&'a self, installed: bool, vm_dir: &Path, guest: GuestOS) -> Result<ImgArgs<'a>, Error> {
         let images = self
@@ -38,6 +40,22 @@ pub(crate) struct ImgArgs<'a> {
     guest: GuestOS,
 }
 
+impl ImgArgs<'_> {
+    pub(crate) fn drives(&self) -> impl Iterator<Item = Drive<'_>> {
+        let bus = match self.guest {
+            GuestOS::MacOS { .. } => DriveBus::Sata,
+            _ => DriveBus::Virtio,
+        };
+        self.images.iter().map(move |img| Drive {
+            path: img,
+            cdrom: false,
+            format: "raw",
+            bus,
+            transient: false,
+        })
+    }
+}
+
 impl EmulatorArgs for ImgArgs<'_> {
     fn display(&self) -> impl IntoIterator<Item = ArgDisplay> {
         self.images.iter().map(|img| ArgDisplay {
@@ -8,6 +8,8 @@ use crate::{
     utils::{ArgDisplay, EmulatorArgs, QemuArg},
 };
 
+use super::{Drive, DriveBus};
+
 impl<'a> Images {
     pub(crate) fn iso_args(&'a self, installed: bool, guest: GuestOS, vm_dir: &Path, used_indices: &mut HashSet<u32>) -> Result<IsoArgs<'a>, Error> {
         let mut key = 0;
@@ -48,6 +50,22 @@ pub(crate) struct IsoArgs<'a> {
     guest: GuestOS,
 }
 
+impl IsoArgs<'_> {
+    pub(crate) fn drives(&self) -> impl Iterator<Item = Drive<'_>> {
+        let bus = match self.guest {
+            GuestOS::ReactOS => DriveBus::Ide,
+            _ => DriveBus::Default,
+        };
+        self.images.iter().map(move |iso| Drive {
+            path: &iso.path,
+            cdrom: true,
+            format: "raw",
+            bus,
+            transient: false,
+        })
+    }
+}
+
 impl EmulatorArgs for IsoArgs<'_> {
     fn display(&self) -> impl IntoIterator<Item = ArgDisplay> {
         self.images.iter().map(|iso| ArgDisplay {
@@ -4,6 +4,8 @@ use itertools::chain;
 use public_dir::PublicDirArgs;
 use usb::USBArgs;
 
+#[cfg(not(target_os = "macos"))]
+use crate::plan::PlannedAction;
 use crate::{
     data::{Arch, GuestOS, Io, Keyboard, KeyboardLayout, Mouse},
     error::{Error, Warning},
@@ -127,4 +129,8 @@ impl EmulatorArgs for IoArgs<'_> {
 
         iter
     }
+    #[cfg(not(target_os = "macos"))]
+    fn planned_actions(&self) -> impl IntoIterator<Item = PlannedAction> {
+        self.spice.as_ref().map(|spice| spice.planned_actions()).into_iter().flatten()
+    }
 }
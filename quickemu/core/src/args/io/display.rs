@@ -101,11 +101,9 @@ enum GpuType {
 
 impl EmulatorArgs for DisplayArgs {
     fn display(&self) -> impl IntoIterator<Item = ArgDisplay> {
-        let resolution_text = if self.gpu != GpuType::VMwareSVGA && !self.fullscreen && self.res.is_some() {
-            let (x, y) = self.res.unwrap();
-            format!(", Resolution: {x}x{y}")
-        } else {
-            "".into()
+        let resolution_text = match self.res {
+            Some((x, y)) if self.gpu != GpuType::VMwareSVGA && !self.fullscreen => format!(", Resolution: {x}x{y}"),
+            _ => "".into(),
         };
         Some(ArgDisplay {
             name: "Display".into(),
@@ -140,11 +138,9 @@ impl EmulatorArgs for DisplayArgs {
         args.extend([arg!("-display"), display_type_arg]);
         args.extend([arg!("-vga"), arg!("none")]);
 
-        let display_device_arg = if self.fullscreen || self.gpu == GpuType::VMwareSVGA || self.res.is_none() {
-            arg!(display_device_arg)
-        } else {
-            let (x, y) = self.res.unwrap();
-            oarg!(format!("{display_device_arg},xres={x},yres={y}"))
+        let display_device_arg = match self.res {
+            Some((x, y)) if !self.fullscreen && self.gpu != GpuType::VMwareSVGA => oarg!(format!("{display_device_arg},xres={x},yres={y}")),
+            _ => arg!(display_device_arg),
         };
         args.extend([arg!("-device"), display_device_arg]);
 
@@ -7,6 +7,7 @@ use crate::{
     data::{Accelerated, Access, Display, DisplayType, GuestOS, Resolution, Viewer},
     error::Error,
     oarg,
+    plan::PlannedAction,
     utils::{find_port, ArgDisplay, EmulatorArgs, LaunchFn, LaunchFnReturn, QemuArg},
 };
 
@@ -14,10 +15,8 @@ impl<'a> Display {
     pub fn spice_args(&self, vm_name: &'a str, guest: GuestOS, public_dir: Option<Cow<'a, str>>) -> Result<SpiceArgs<'a>, Error> {
         match self.display_type {
             DisplayType::SpiceApp => Ok(SpiceArgs::SpiceApp { accelerated: self.accelerated }),
-            DisplayType::Spice { access, spice_port, viewer } => {
-                let Some(port) = find_port(spice_port, 9) else {
-                    return Err(Error::UnavailablePort(spice_port));
-                };
+            // The port was already moved to a free one when the configuration was finalized
+            DisplayType::Spice { access, spice_port: port, viewer } => {
                 let public_dir = public_dir.and_then(|dir| (!matches!(guest, GuestOS::MacOS { .. })).then_some(dir));
                 let fullscreen = matches!(self.resolution, Resolution::FullScreen);
                 Ok(SpiceArgs::Spice {
@@ -34,6 +33,17 @@ impl<'a> Display {
     }
 }
 
+impl Display {
+    // Like the SSH port, a SPICE port which is in use is moved to the next free one up front,
+    // so that the launch report, hooks and live VM file are given the port QEMU listens on
+    pub(crate) fn assign_spice_port(&mut self) -> Result<(), Error> {
+        if let DisplayType::Spice { spice_port, .. } = &mut self.display_type {
+            *spice_port = find_port(*spice_port, 9).ok_or(Error::UnavailablePort(*spice_port))?;
+        }
+        Ok(())
+    }
+}
+
 pub enum SpiceArgs<'a> {
     SpiceApp {
         accelerated: Accelerated,
@@ -151,4 +161,17 @@ impl EmulatorArgs for SpiceArgs<'_> {
             _ => None,
         }
     }
+    fn planned_actions(&self) -> impl IntoIterator<Item = PlannedAction> {
+        match self {
+            Self::Spice { viewer, port, .. } => {
+                let viewer = match viewer {
+                    Viewer::Spicy => "spicy",
+                    Viewer::Remote => "remote-viewer",
+                    _ => return None,
+                };
+                Some(PlannedAction::LaunchViewer { viewer, port: *port })
+            }
+            _ => None,
+        }
+    }
 }
@@ -5,12 +5,14 @@ use cpu::Cpu;
 use itertools::chain;
 use ram::Ram;
 use tpm::Tpm;
+use watchdog::Watchdog;
 
 use crate::{
     arg,
     data::{AArch64Machine, Arch, BootType, GuestOS, Machine, Riscv64Machine, X86_64Machine},
     error::{Error, Warning},
     oarg,
+    plan::PlannedAction,
     utils::{ArgDisplay, EmulatorArgs, LaunchFn, QemuArg},
 };
 
@@ -18,9 +20,10 @@ mod boot;
 mod cpu;
 mod ram;
 mod tpm;
+mod watchdog;
 
 impl Machine {
-    pub fn args(&self, guest: GuestOS, vm_dir: &Path, vm_name: &str) -> Result<(MachineArgs, Vec<Warning>), Error> {
+    pub fn args(&self, guest: GuestOS, vm_dir: &Path, vm_name: &str, dry_run: bool) -> Result<(MachineArgs, Vec<Warning>), Error> {
         let mut warnings = Vec::new();
         let (cpu_args, cpu_warnings) = self.cpu_args(guest)?;
         warnings.extend(cpu_warnings);
@@ -29,8 +32,9 @@ impl Machine {
         warnings.extend(ram_warning);
 
         let tpm_args = self.tpm.then(|| Tpm::new(vm_dir, vm_name)).transpose()?;
-        let boot_args = self.boot_args(vm_dir, guest)?;
+        let boot_args = self.boot_args(vm_dir, guest, dry_run)?;
         let machine_type = FullMachine::new(self.arch, guest, self.boot);
+        let watchdog = self.watchdog.map(Watchdog);
 
         Ok((
             MachineArgs {
@@ -39,18 +43,32 @@ impl Machine {
                 tpm_args,
                 boot_args,
                 machine_type,
+                watchdog,
             },
             warnings,
         ))
     }
 }
 
+impl Machine {
+    // The QEMU machine type, and whether SMM is enabled and the HPET disabled on it
+    pub(crate) fn machine_type(&self, guest: GuestOS) -> (&'static str, bool, bool) {
+        let machine = FullMachine::new(self.arch, guest, self.boot);
+        let (smm, no_hpet) = match machine.specific {
+            MachineType::X86_64 { smm, no_hpet } => (smm, no_hpet),
+            _ => (false, false),
+        };
+        (machine.qemu_machine.name(), smm, no_hpet)
+    }
+}
+
 pub struct MachineArgs {
     cpu_args: Cpu,
     ram_args: Ram,
     tpm_args: Option<Tpm>,
     boot_args: BootArgs,
     machine_type: FullMachine,
+    watchdog: Option<Watchdog>,
 }
 
 impl EmulatorArgs for MachineArgs {
@@ -61,6 +79,7 @@ impl EmulatorArgs for MachineArgs {
             self.tpm_args.as_ref().map(|tpm| tpm.display()).into_iter().flatten(),
             self.boot_args.display(),
             self.machine_type.display(),
+            self.watchdog.as_ref().map(|watchdog| watchdog.display()).into_iter().flatten(),
         )
     }
     fn qemu_args(&self) -> impl IntoIterator<Item = QemuArg> {
@@ -70,6 +89,11 @@ impl EmulatorArgs for MachineArgs {
             self.tpm_args.as_ref().map(|tpm| tpm.qemu_args()).into_iter().flatten(),
             self.boot_args.qemu_args(),
             self.machine_type.qemu_args(),
+            self.watchdog
+                .as_ref()
+                .map(|watchdog| watchdog.qemu_args())
+                .into_iter()
+                .flatten(),
         )
     }
     fn launch_fns(self) -> impl IntoIterator<Item = LaunchFn> {
@@ -81,6 +105,12 @@ impl EmulatorArgs for MachineArgs {
             self.machine_type.launch_fns(),
         )
     }
+    fn planned_actions(&self) -> impl IntoIterator<Item = PlannedAction> {
+        chain!(
+            self.tpm_args.as_ref().map(|tpm| tpm.planned_actions()).into_iter().flatten(),
+            self.boot_args.planned_actions(),
+        )
+    }
 }
 
 struct FullMachine {
@@ -158,10 +188,13 @@ enum QemuMachineType {
 
 impl QemuMachineType {
     fn arg(&self) -> OsString {
+        self.name().into()
+    }
+    fn name(&self) -> &'static str {
         match self {
-            Self::Qemu32 => "q35".into(),
-            Self::Pc => "pc".into(),
-            Self::Virt => "virt".into(),
+            Self::Qemu32 => "q35",
+            Self::Pc => "pc",
+            Self::Virt => "virt",
         }
     }
 }
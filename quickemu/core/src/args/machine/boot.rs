@@ -9,6 +9,7 @@ use crate::{
     data::{Arch, BootType, GuestOS, Machine},
     error::Error,
     oarg,
+    plan::PlannedAction,
     utils::{ArgDisplay, EmulatorArgs, QemuArg},
 };
 
@@ -37,7 +38,7 @@ const AARCH64_OVMF: [(&str, &str); 1] = [("AAVMF/AAVMF_CODE.fd", "AAVMF/AAVMF_VA
 const RISCV64_UBOOT: [&str; 1] = ["/usr/lib/u-boot/qemu-riscv64_smode/u-boot.bin"];
 
 impl Machine {
-    pub(crate) fn boot_args(&self, vm_dir: &Path, guest: GuestOS) -> Result<BootArgs, Error> {
+    pub(crate) fn boot_args(&self, vm_dir: &Path, guest: GuestOS, dry_run: bool) -> Result<BootArgs, Error> {
         match (&self.boot, self.arch) {
             (BootType::Efi { secure_boot: false }, Arch::X86_64 { .. }) if matches!(guest, GuestOS::MacOS { .. }) => macos_firmware(vm_dir),
             _ if matches!(guest, GuestOS::MacOS { .. }) => Err(Error::UnsupportedBootCombination),
@@ -46,9 +47,9 @@ impl Machine {
             (BootType::Efi { secure_boot: _ }, Arch::Riscv64 { .. }) => find_riscv64_bios(vm_dir),
             (BootType::Efi { secure_boot }, Arch::X86_64 { .. }) => {
                 let ovmf = if *secure_boot { SECURE_BOOT_OVMF } else { EFI_OVMF };
-                standard_firmware(vm_dir, *secure_boot, ovmf).map(BootArgs::X86_64Efi)
+                standard_firmware(vm_dir, *secure_boot, ovmf, dry_run).map(BootArgs::X86_64Efi)
             }
-            (BootType::Efi { secure_boot: false }, Arch::AArch64 { .. }) => standard_firmware(vm_dir, false, AARCH64_OVMF.as_slice()).map(BootArgs::AArch64Efi),
+            (BootType::Efi { secure_boot: false }, Arch::AArch64 { .. }) => standard_firmware(vm_dir, false, AARCH64_OVMF.as_slice(), dry_run).map(BootArgs::AArch64Efi),
             _ => Err(Error::UnsupportedBootCombination),
         }
     }
@@ -65,6 +66,24 @@ pub(crate) struct Efi {
     code: PathBuf,
     vars: PathBuf,
     secure_boot: bool,
+    // Set in dry runs, when the VM's OVMF vars would have been copied from this file
+    copy_vars_from: Option<PathBuf>,
+}
+
+impl BootArgs {
+    // OVMF code and variables, and whether Secure Boot is enabled
+    pub(crate) fn efi(&self) -> Option<(&Path, &Path, bool)> {
+        match self {
+            Self::X86_64Efi(Efi { code, vars, secure_boot, .. }) | Self::AArch64Efi(Efi { code, vars, secure_boot, .. }) => Some((code, vars, *secure_boot)),
+            _ => None,
+        }
+    }
+    pub(crate) fn kernel(&self) -> Option<&Path> {
+        match self {
+            Self::Riscv64Efi(bootloader) => Some(bootloader),
+            _ => None,
+        }
+    }
 }
 
 impl EmulatorArgs for BootArgs {
@@ -112,6 +131,14 @@ impl EmulatorArgs for BootArgs {
             }
         }
     }
+    fn planned_actions(&self) -> impl IntoIterator<Item = PlannedAction> {
+        match self {
+            Self::X86_64Efi(Efi { vars, copy_vars_from: Some(from), .. }) | Self::AArch64Efi(Efi { vars, copy_vars_from: Some(from), .. }) => {
+                Some(PlannedAction::CopyOvmfVars { from: from.clone(), to: vars.clone() })
+            }
+            _ => None,
+        }
+    }
 }
 
 fn find_riscv64_bios(vm_dir: &Path) -> Result<BootArgs, Error> {
@@ -146,7 +173,7 @@ fn qemu_share_dir() -> PathBuf {
     PathBuf::from("/usr/share")
 }
 
-fn standard_firmware(vm_dir: &Path, secure_boot: bool, ovmfs: &[(&str, &str)]) -> Result<Efi, Error> {
+fn standard_firmware(vm_dir: &Path, secure_boot: bool, ovmfs: &[(&str, &str)], dry_run: bool) -> Result<Efi, Error> {
     let vm_vars = vm_dir.join("OVMF_VARS.fd");
 
     let share_dir = qemu_share_dir();
@@ -155,14 +182,24 @@ fn standard_firmware(vm_dir: &Path, secure_boot: bool, ovmfs: &[(&str, &str)]) -
         .map(|(code, vars)| (share_dir.join(code), share_dir.join(vars)))
         .find(|(code, vars)| code.exists() && vars.exists())
         .map(|(code, vars)| {
+            let mut copy_vars_from = None;
             if !vm_vars.exists() || vm_vars.metadata().is_ok_and(|m| m.permissions().readonly()) {
-                std::fs::copy(vars, &vm_vars).map_err(|e| Error::CopyOvmfVars(e.to_string()))?;
+                if dry_run {
+                    copy_vars_from = Some(vars);
+                } else {
+                    std::fs::copy(vars, &vm_vars).map_err(|e| Error::CopyOvmfVars(e.to_string()))?;
+                }
             }
             let code = code.canonicalize().expect("OVMF Code should be a valid path");
-            Ok::<_, Error>((code, vm_vars))
+            Ok::<_, Error>((code, vm_vars, copy_vars_from))
         })
         .transpose()?
-        .map(|(code, vars)| Efi { code, vars, secure_boot })
+        .map(|(code, vars, copy_vars_from)| Efi {
+            code,
+            vars,
+            secure_boot,
+            copy_vars_from,
+        })
         .ok_or(Error::Ovmf)
 }
 
@@ -177,5 +214,10 @@ fn macos_firmware(vm_dir: &Path) -> Result<BootArgs, Error> {
         .find(|vars| vars.exists())
         .ok_or(Error::Ovmf)?;
 
-    Ok(BootArgs::X86_64Efi(Efi { code, vars, secure_boot: false }))
+    Ok(BootArgs::X86_64Efi(Efi {
+        code,
+        vars,
+        secure_boot: false,
+        copy_vars_from: None,
+    }))
 }
@@ -103,6 +103,13 @@ pub(crate) struct Cpu {
     cpu_type: Option<CpuArg>,
 }
 
+impl Cpu {
+    // Sockets, cores per socket and threads per core, as passed to `-smp`
+    pub(crate) fn topology(&self) -> (usize, usize, usize) {
+        (self.unique_cpus.len(), self.phys_cores, if self.smt { 2 } else { 1 })
+    }
+}
+
 impl EmulatorArgs for Cpu {
     fn display(&self) -> impl IntoIterator<Item = ArgDisplay> {
         let sockets = self.unique_cpus.len();
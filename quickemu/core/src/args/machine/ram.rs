@@ -51,6 +51,12 @@ impl Machine {
     }
 }
 
+impl Ram {
+    pub(crate) fn bytes(&self) -> u64 {
+        self.ram.bytes() as u64
+    }
+}
+
 impl EmulatorArgs for Ram {
     fn display(&self) -> impl IntoIterator<Item = ArgDisplay> {
         Some(ArgDisplay {
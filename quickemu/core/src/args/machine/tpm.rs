@@ -8,20 +8,50 @@ use which::which;
 
 use crate::{
     arg,
+    data::Machine,
     error::Error,
     oarg,
-    utils::{ArgDisplay, EmulatorArgs, LaunchFn, LaunchFnReturn},
+    plan::PlannedAction,
+    utils::{ArgDisplay, EmulatorArgs, LaunchFn, LaunchFnReturn, QemuArg},
 };
 
 #[cfg(not(feature = "inbuilt_commands"))]
 use std::process::Command;
 
+impl Machine {
+    // The socket swtpm listens on for QEMU, when a TPM is enabled
+    pub(crate) fn tpm_socket(&self, vm_dir: &Path, vm_name: &str) -> Option<PathBuf> {
+        self.tpm.then(|| socket_path(vm_dir, vm_name))
+    }
+
+    // The full swtpm command line, run as a daemon, for when QEMU is started by another service manager
+    pub(crate) fn swtpm_daemon_command(&self, vm_dir: &Path, vm_name: &str) -> Result<Option<Vec<OsString>>, Error> {
+        let Some(tpm) = self.tpm.then(|| Tpm::new(vm_dir, vm_name)).transpose()? else {
+            return Ok(None);
+        };
+        #[cfg(not(feature = "inbuilt_commands"))]
+        let binary = tpm.binary.as_os_str();
+        #[cfg(feature = "inbuilt_commands")]
+        let binary = OsStr::new("swtpm");
+        let command = std::iter::once(binary)
+            .chain(tpm.swtpm_args())
+            .chain([OsStr::new("--daemon")])
+            .map(OsStr::to_os_string)
+            .collect();
+        Ok(Some(command))
+    }
+}
+
+fn socket_path(vm_dir: &Path, vm_name: &str) -> PathBuf {
+    vm_dir.join(format!("{vm_name}.swtpm-sock"))
+}
+
 impl Tpm {
     pub(crate) fn new(vm_dir: &Path, vm_name: &str) -> Result<Tpm, Error> {
         #[cfg(not(feature = "inbuilt_commands"))]
         let binary = which("swtpm")?;
 
-        let socket = vm_dir.join(format!("{vm_name}.swtpm-sock"));
+        let socket = socket_path(vm_dir, vm_name);
 
         let mut ctrl = OsString::from("type=unixio,path=");
         ctrl.push(&socket);
@@ -47,31 +77,45 @@ pub(crate) struct Tpm {
     socket: PathBuf,
 }
 
+impl Tpm {
+    fn swtpm_args(&self) -> [&OsStr; 7] {
+        [
+            OsStr::new("socket"),
+            OsStr::new("--ctrl"),
+            &self.ctrl,
+            OsStr::new("--terminate"),
+            OsStr::new("--tpmstate"),
+            &self.tpmstate,
+            OsStr::new("--tpm2"),
+        ]
+    }
+}
+
 impl EmulatorArgs for Tpm {
+    fn qemu_args(&self) -> impl IntoIterator<Item = QemuArg> {
+        let mut chardev = OsString::from("socket,id=chrtpm,path=");
+        chardev.push(&self.socket);
+        [
+            arg!("-chardev"),
+            oarg!(chardev),
+            arg!("-tpmdev"),
+            arg!("emulator,id=tpm0,chardev=chrtpm"),
+            arg!("-device"),
+            arg!("tpm-tis,tpmdev=tpm0"),
+        ]
+    }
+    fn planned_actions(&self) -> impl IntoIterator<Item = PlannedAction> {
+        Some(PlannedAction::StartSwtpm {
+            #[cfg(not(feature = "inbuilt_commands"))]
+            binary: self.binary.clone(),
+            #[cfg(feature = "inbuilt_commands")]
+            binary: PathBuf::from("swtpm"),
+            socket: self.socket.clone(),
+        })
+    }
     fn launch_fns(self) -> impl IntoIterator<Item = LaunchFn> {
         let tpm_launch = move || {
-            let tpm_args = [
-                OsStr::new("socket"),
-                OsStr::new("--ctrl"),
-                &self.ctrl,
-                OsStr::new("--terminate"),
-                OsStr::new("--tpmstate"),
-                &self.tpmstate,
-                OsStr::new("--tpm2"),
-            ];
-
-            let args = |socket: PathBuf| {
-                [
-                    arg!("-chardev"),
-                    oarg!(socket),
-                    arg!("-tpmdev"),
-                    arg!("emulator,id=tpm0,chardev=chrtpm"),
-                    arg!("-device"),
-                    arg!("tpm-tis,tpmdev=tpm0"),
-                ]
-                .into_iter()
-                .map(LaunchFnReturn::Arg)
-            };
+            let tpm_args = self.swtpm_args();
 
             #[cfg(not(feature = "inbuilt_commands"))]
             {
@@ -81,16 +125,13 @@ impl EmulatorArgs for Tpm {
                     .map_err(|e| Error::Command("swtpm", e.to_string()))?;
                 let pid = child.id();
 
-                Ok([
+                Ok(vec![
                     LaunchFnReturn::Process(child),
                     LaunchFnReturn::Display(ArgDisplay {
                         name: Cow::Borrowed("TPM"),
                         value: Cow::Owned(format!("{} (pid: {})", self.socket.display(), pid)),
                     }),
-                ]
-                .into_iter()
-                .chain(args(self.socket))
-                .collect())
+                ])
             }
             #[cfg(feature = "inbuilt_commands")]
             {
@@ -0,0 +1,22 @@
+use std::borrow::Cow;
+
+use crate::{
+    arg,
+    data::WatchdogAction,
+    oarg,
+    utils::{ArgDisplay, EmulatorArgs, QemuArg},
+};
+
+pub(crate) struct Watchdog(pub(crate) WatchdogAction);
+
+impl EmulatorArgs for Watchdog {
+    fn display(&self) -> impl IntoIterator<Item = ArgDisplay> {
+        Some(ArgDisplay {
+            name: Cow::Borrowed("Watchdog"),
+            value: Cow::Owned(format!("i6300esb ({})", self.0)),
+        })
+    }
+    fn qemu_args(&self) -> impl IntoIterator<Item = QemuArg> {
+        [arg!("-device"), arg!("i6300esb"), arg!("-action"), oarg!(format!("watchdog={}", self.0))]
+    }
+}
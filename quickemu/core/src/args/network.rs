@@ -1,21 +1,27 @@
-use std::{borrow::Cow, ffi::OsString, path::Path};
+use std::{
+    borrow::Cow,
+    ffi::OsString,
+    net::{Ipv4Addr, SocketAddrV4},
+    path::{Path, PathBuf},
+};
 
 use itertools::chain;
 use which::which;
 
 use crate::{
     arg,
-    data::{GuestOS, MacOSRelease, Monitor, Network, NetworkType, PortForward, Serial},
+    data::{Cidr, GuestOS, MacOSRelease, Monitor, NatSettings, NetDevice, Network, NetworkInterface, NetworkType, PortForward, PrivateBackend, Protocol, Serial},
     error::{Error, Warning},
+    host_network::interface_exists,
     oarg,
-    utils::{ArgDisplay, EmulatorArgs, LaunchFn, QemuArg},
+    utils::{find_port, port_available, ArgDisplay, EmulatorArgs, LaunchFn, QemuArg},
 };
 
 mod monitor;
 
 impl<'a> Network {
-    pub(crate) fn args(&'a self, guest: GuestOS, vm_name: &'a str, publicdir: Option<&'a Path>) -> Result<(FullNetworkArgs<'a>, Option<Warning>), Error> {
-        let network_args = self.inner_args(guest, vm_name, publicdir);
+    pub(crate) fn args(&'a self, guest: GuestOS, vm_dir: &'a Path, vm_name: &'a str, publicdir: Option<&'a Path>, dry_run: bool) -> Result<(FullNetworkArgs<'a>, Option<Warning>), Error> {
+        let network_args = self.inner_args(guest, vm_dir, vm_name, publicdir, dry_run);
         Ok((
             FullNetworkArgs {
                 network: network_args,
@@ -26,17 +32,299 @@ impl<'a> Network {
         ))
     }
 
-    fn inner_args(&'a self, guest: GuestOS, vm_name: &'a str, publicdir: Option<&'a Path>) -> NetworkArgs<'a> {
-        let samba = if matches!(self.network_type, NetworkType::Nat { .. }) {
-            which("smbd").ok().and(publicdir)
-        } else {
-            None
-        };
-        NetworkArgs {
-            network_type: &self.network_type,
-            network_device: guest.into(),
-            vm_name,
-            samba,
+    fn inner_args(&'a self, guest: GuestOS, vm_dir: &'a Path, vm_name: &'a str, publicdir: Option<&'a Path>, dry_run: bool) -> NetworkArgs<'a> {
+        // Samba is only shared through the first NAT interface
+        let samba_interface = self
+            .interfaces
+            .iter()
+            .position(|interface| matches!(interface.network_type, NetworkType::Nat { .. }));
+        let samba = samba_interface.and(which("smbd").ok()).and(publicdir);
+
+        let interfaces = self
+            .interfaces
+            .iter()
+            .enumerate()
+            .filter(|(_, interface)| !matches!(interface.network_type, NetworkType::None))
+            .map(|(index, interface)| {
+                let id = format!("nic{index}");
+                InterfaceArgs {
+                    interface,
+                    mac_addr: interface.mac_addr.clone().or_else(|| interface.private_mac_addr(vm_name, &id)),
+                    id,
+                    network_device: interface.net_device(guest),
+                    vm_dir,
+                    vm_name,
+                    samba: samba.filter(|_| samba_interface == Some(index)),
+                    private: PrivateArgs::new(&interface.network_type, dry_run),
+                }
+            })
+            .collect();
+        NetworkArgs { interfaces }
+    }
+
+    // Each NAT interface picks a free SSH port when the configuration is read, so interfaces after the first would pick the same one
+    pub(crate) fn assign_ssh_ports(&mut self) {
+        let mut used = self
+            .port_forwards()
+            .filter(|forward| forward.protocol == Protocol::Tcp)
+            .flat_map(|forward| forward.host.ports())
+            .collect::<Vec<_>>();
+        for interface in &mut self.interfaces {
+            let NetworkType::Nat { ssh_port, .. } = &mut interface.network_type else { continue };
+            let Some(port) = *ssh_port.as_ref() else { continue };
+            if used.contains(&port) {
+                let mut next = port;
+                *ssh_port.as_mut() = loop {
+                    match find_port(next + 1, 9) {
+                        Some(port) if used.contains(&port) => next = port,
+                        port => break port,
+                    }
+                };
+            }
+            used.extend(*ssh_port.as_ref());
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        self.interfaces
+            .iter()
+            .try_for_each(|interface| interface.validate().map_err(|(_, err)| err))?;
+        self.validate_port_forwards()
+    }
+
+    // Forwards on different interfaces can also conflict, as they all listen on the host
+    pub(crate) fn validate_port_forwards(&self) -> Result<(), Error> {
+        let mut used: Vec<(Protocol, Option<Ipv4Addr>, u16)> = Vec::new();
+        for forward in self.port_forwards() {
+            for (host, _) in forward.ports() {
+                let conflict = used
+                    .iter()
+                    .any(|(protocol, addr, port)| *protocol == forward.protocol && *port == host && (addr.is_none() || forward.host_addr.is_none() || *addr == forward.host_addr));
+                if conflict {
+                    return Err(Error::PortForwardConflict(forward.protocol.to_string(), host));
+                }
+                used.push((forward.protocol, forward.host_addr, host));
+            }
+        }
+        Ok(())
+    }
+
+    // Only checked when QEMU is launched, as a running VM holds its own forwarded ports. Ports which are already in use
+    // are reported rather than moved, unlike the SSH port, as the guest's services are expected at them
+    pub(crate) fn check_port_forwards_available(&self) -> Result<(), Error> {
+        for forward in self.port_forwards() {
+            let host_addr = forward.host_addr.unwrap_or(Ipv4Addr::UNSPECIFIED);
+            if let Some(port) = forward
+                .host
+                .ports()
+                .find(|port| !port_available(forward.protocol, host_addr, *port))
+            {
+                return Err(Error::UnavailablePort(port));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl NatSettings {
+    const DEFAULT_CIDR: Cidr<Ipv4Addr> = Cidr {
+        addr: Ipv4Addr::new(10, 0, 2, 0),
+        prefix: 24,
+    };
+
+    fn network(&self) -> Cidr<Ipv4Addr> {
+        self.cidr.unwrap_or(Self::DEFAULT_CIDR)
+    }
+
+    // QEMU places its own defaults at fixed offsets within the network, e.g. the gateway at .2
+    fn network_addr(&self, offset: u32) -> Ipv4Addr {
+        let network = self.network();
+        Ipv4Addr::from(u32::from(network.addr) & netmask(network.prefix) | offset)
+    }
+
+    // The address the guest is given through DHCP, which port forwards reach by default
+    pub(crate) fn guest_addr(&self) -> Ipv4Addr {
+        self.dhcp_start.unwrap_or_else(|| self.network_addr(15))
+    }
+
+    pub(crate) fn samba_addr(&self) -> Ipv4Addr {
+        self.network_addr(4)
+    }
+
+    // Addresses QEMU derives from the network are always within it, so only those which are set are checked
+    fn validate(&self) -> Result<(), (String, Error)> {
+        [("host_addr", self.host_addr), ("dns", self.dns), ("dhcp_start", self.dhcp_start)]
+            .into_iter()
+            .filter_map(|(key, addr)| addr.map(|addr| (key, addr)))
+            .try_for_each(|(key, addr)| self.check_addr(addr).map_err(|err| (key.to_string(), err)))
+    }
+
+    // Options for `-netdev user`, other than the TFTP directory, which is resolved against the VM directory
+    fn qemu_options(&self) -> String {
+        let mut options = String::new();
+        if let Some(cidr) = self.cidr {
+            options.push_str(&format!(",net={cidr}"));
+        }
+        if let Some(host_addr) = self.host_addr {
+            options.push_str(&format!(",host={host_addr}"));
+        }
+        if let Some(dhcp_start) = self.dhcp_start {
+            options.push_str(&format!(",dhcpstart={dhcp_start}"));
+        }
+        if let Some(dns) = self.dns {
+            options.push_str(&format!(",dns={dns}"));
+        }
+        for domain in &self.dns_search {
+            options.push_str(&format!(",dnssearch={domain}"));
+        }
+        if !self.ipv6 {
+            options.push_str(",ipv6=off");
+        }
+        if let Some(prefix) = self.ipv6_prefix {
+            options.push_str(&format!(",ipv6-net={prefix}"));
+        }
+        if let Some(bootfile) = &self.bootfile {
+            options.push_str(&format!(",bootfile={bootfile}"));
+        }
+        options
+    }
+
+    fn check_addr(&self, addr: Ipv4Addr) -> Result<(), Error> {
+        let network = self.network();
+        let mask = netmask(network.prefix);
+        match u32::from(addr) & mask == u32::from(network.addr) & mask {
+            true => Ok(()),
+            false => Err(Error::NatAddress(addr.to_string(), network.to_string())),
+        }
+    }
+}
+
+fn netmask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)
+}
+
+impl PortForward {
+    fn validate(&self) -> Result<(), Error> {
+        if self.guest.count() != 1 && self.guest.count() != self.host.count() {
+            return Err(Error::PortForwardRange(self.host.to_string(), self.guest.to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl NetworkInterface {
+    // Host devices are checked up front, as QEMU's errors for them are unclear.
+    // Errors are returned along with the key of the offending setting, relative to the interface
+    pub(crate) fn validate(&self) -> Result<(), (String, Error)> {
+        match &self.network_type {
+            NetworkType::Bridged { bridge } if !interface_exists(bridge.as_ref()) => Err(("bridge".into(), Error::MissingBridge(bridge.as_ref().clone()))),
+            NetworkType::Tap { tap, .. } if !interface_exists(tap) => Err(("tap".into(), Error::MissingTap(tap.clone()))),
+            NetworkType::Nat { port_forwards, settings, .. } => {
+                settings.validate()?;
+                for (index, forward) in port_forwards.iter().enumerate() {
+                    forward
+                        .validate()
+                        .map_err(|err| (format!("port_forwards[{index}].guest"), err))?;
+                    if let Some(guest_addr) = forward.guest_addr {
+                        settings
+                            .check_addr(guest_addr)
+                            .map_err(|err| (format!("port_forwards[{index}].guest_addr"), err))?;
+                    }
+                }
+                Ok(())
+            }
+            // Private network names are also used as socket file names
+            NetworkType::Private { name, .. } => {
+                let valid = !name.is_empty() && !name.starts_with('.') && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+                match valid {
+                    true => Ok(()),
+                    false => Err(("name".into(), Error::InvalidNetworkName(name.clone()))),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The NIC model QEMU is given for the guest
+    pub(crate) fn net_device(&self, guest: GuestOS) -> NetDevice {
+        self.model.unwrap_or(guest.default_net_device())
+    }
+
+    // QEMU gives the same addresses to every VM's interfaces, so VMs sharing a private network would otherwise conflict
+    fn private_mac_addr(&self, vm_name: &str, id: &str) -> Option<String> {
+        matches!(self.network_type, NetworkType::Private { .. }).then(|| {
+            let [.., a, b, c] = stable_hash(&format!("{vm_name}/{id}")).to_be_bytes();
+            format!("52:54:00:{a:02x}:{b:02x}:{c:02x}")
+        })
+    }
+}
+
+// The multicast group used by a private network, unless one is configured
+pub(crate) fn private_multicast_addr(name: &str) -> SocketAddrV4 {
+    let [.., port_high, port_low, group_high, group_low] = stable_hash(name).to_be_bytes();
+    let port = 49152 + u16::from_be_bytes([port_high, port_low]) % 16384;
+    SocketAddrV4::new(Ipv4Addr::new(239, 255, group_high, group_low), port)
+}
+
+// The socket shared by both VMs on a private network using the unix backend
+pub(crate) fn private_socket_path(name: &str) -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("quickemu")
+        .join("networks")
+        .join(format!("{name}.socket"))
+}
+
+// FNV-1a, which unlike std's hashers is guaranteed to be stable between builds
+fn stable_hash(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+enum PrivateArgs {
+    Multicast(SocketAddrV4),
+    Unix { path: PathBuf, listen: bool },
+}
+
+impl PrivateArgs {
+    fn new(network_type: &NetworkType, dry_run: bool) -> Option<Self> {
+        let NetworkType::Private { name, backend, address } = network_type else { return None };
+        Some(match backend {
+            PrivateBackend::Multicast => Self::Multicast(address.unwrap_or_else(|| private_multicast_addr(name))),
+            PrivateBackend::Unix => {
+                let path = private_socket_path(name);
+                // The first VM to start listens, and the other connects to it. Dry runs don't look for another VM, and assume this one starts first
+                #[cfg(unix)]
+                let listen = dry_run || std::os::unix::net::UnixStream::connect(&path).is_err();
+                #[cfg(not(unix))]
+                let listen = true;
+                Self::Unix { path, listen }
+            }
+        })
+    }
+}
+
+impl GuestOS {
+    pub(crate) fn default_net_device(&self) -> NetDevice {
+        match self {
+            GuestOS::ReactOS => NetDevice::E1000,
+            GuestOS::MacOS { release } if *release >= MacOSRelease::BigSur => NetDevice::VirtioNet,
+            GuestOS::MacOS { .. } => NetDevice::Vmxnet3,
+            GuestOS::Linux | GuestOS::LinuxOld | GuestOS::Solaris | GuestOS::GhostBSD | GuestOS::FreeBSD | GuestOS::GenericBSD => NetDevice::VirtioNet,
+            _ => NetDevice::Rtl8139,
+        }
+    }
+}
+
+impl NetDevice {
+    // The QEMU device model
+    pub(crate) fn model(&self) -> &'static str {
+        match self {
+            Self::E1000 => "e1000",
+            Self::Vmxnet3 => "vmxnet3",
+            Self::VirtioNet => "virtio-net",
+            Self::Rtl8139 => "rtl8139",
         }
     }
 }
@@ -60,31 +348,80 @@ impl EmulatorArgs for FullNetworkArgs<'_> {
 }
 
 struct NetworkArgs<'a> {
-    network_type: &'a NetworkType,
+    interfaces: Vec<InterfaceArgs<'a>>,
+}
+
+impl EmulatorArgs for NetworkArgs<'_> {
+    fn display(&self) -> impl IntoIterator<Item = ArgDisplay> {
+        if self.interfaces.is_empty() {
+            return vec![ArgDisplay {
+                name: Cow::Borrowed("Network"),
+                value: Cow::Borrowed("Disabled"),
+            }];
+        }
+        self.interfaces.iter().flat_map(InterfaceArgs::display).collect()
+    }
+    fn qemu_args(&self) -> impl IntoIterator<Item = QemuArg> {
+        if self.interfaces.is_empty() {
+            return vec![arg!("-nic"), arg!("none")];
+        }
+        self.interfaces.iter().flat_map(InterfaceArgs::qemu_args).collect()
+    }
+    fn launch_fns(self) -> impl IntoIterator<Item = LaunchFn> {
+        self.interfaces.into_iter().flat_map(InterfaceArgs::launch_fns)
+    }
+}
+
+struct InterfaceArgs<'a> {
+    interface: &'a NetworkInterface,
+    // The netdev ID, unique to each interface
+    id: String,
+    mac_addr: Option<String>,
     network_device: NetDevice,
+    vm_dir: &'a Path,
     vm_name: &'a str,
     samba: Option<&'a Path>,
+    private: Option<PrivateArgs>,
 }
 
-impl EmulatorArgs for NetworkArgs<'_> {
+impl EmulatorArgs for InterfaceArgs<'_> {
     fn display(&self) -> impl IntoIterator<Item = ArgDisplay> {
-        let network_type = match self.network_type {
-            NetworkType::None => Cow::Borrowed("Disabled"),
-            NetworkType::Nat { restrict: true, .. } => Cow::Owned(format!("Restricted ({})", self.network_device)),
-            NetworkType::Nat { restrict: false, .. } => Cow::Owned(format!("User ({})", self.network_device)),
-            NetworkType::Bridged { bridge, .. } => Cow::Owned(format!("Bridged ({})", bridge.as_ref())),
+        let network_type = match &self.interface.network_type {
+            NetworkType::None => unreachable!("Disabled interfaces should not have arguments"),
+            NetworkType::Nat {
+                restrict,
+                settings: NatSettings { cidr: Some(cidr), .. },
+                ..
+            } => format!(
+                "{} ({}, {cidr})",
+                if *restrict { "Restricted" } else { "User" },
+                self.network_device
+            ),
+            NetworkType::Nat { restrict: true, .. } => format!("Restricted ({})", self.network_device),
+            NetworkType::Nat { restrict: false, .. } => format!("User ({})", self.network_device),
+            NetworkType::Bridged { bridge } => format!("Bridged ({}, {})", bridge.as_ref(), self.network_device),
+            NetworkType::Tap { tap, .. } => format!("TAP ({tap}, {})", self.network_device),
+            NetworkType::Private { name, backend, .. } => format!("Private ({name}, {backend}, {})", self.network_device),
         };
 
         let network_msg = ArgDisplay {
             name: Cow::Borrowed("Network"),
-            value: network_type,
+            value: Cow::Owned(network_type),
         };
 
-        if let NetworkType::Nat { ssh_port, port_forwards, .. } = &self.network_type {
+        if let NetworkType::Nat {
+            ssh_port, port_forwards, settings, ..
+        } = &self.interface.network_type
+        {
+            // The guest's address is only shown when it differs from QEMU's default
+            let guest_addr = match settings.cidr.is_some() || settings.dhcp_start.is_some() {
+                true => format!(" (guest {})", settings.guest_addr()),
+                false => String::new(),
+            };
             let ssh_msg = match ssh_port.as_ref() {
                 Some(port) => ArgDisplay {
                     name: Cow::Borrowed("SSH (Host)"),
-                    value: Cow::Owned(format!("ssh {{user}}@localhost -p {port}")),
+                    value: Cow::Owned(format!("ssh {{user}}@localhost -p {port}{guest_addr}")),
                 },
                 None => ArgDisplay {
                     name: Cow::Borrowed("SSH"),
@@ -94,31 +431,47 @@ impl EmulatorArgs for NetworkArgs<'_> {
 
             let samba_msg = self.samba.map(|_| ArgDisplay {
                 name: Cow::Borrowed("Samba (Guest)"),
-                value: Cow::Borrowed("`smb://10.0.2.4/qemu`"),
+                value: Cow::Owned(format!("`smb://{}/qemu`", settings.samba_addr())),
             });
 
-            let port_forwards = port_forwards.iter().map(|PortForward { host, guest }| ArgDisplay {
+            let port_forwards = port_forwards.iter().map(|forward| ArgDisplay {
                 name: Cow::Borrowed("Port Forward"),
-                value: Cow::Owned(format!("{host} => {guest}")),
+                value: Cow::Owned(forward.to_string()),
             });
             chain!(std::iter::once(network_msg), std::iter::once(ssh_msg), samba_msg, port_forwards).collect()
+        } else if let Some(private) = &self.private {
+            let value = match private {
+                PrivateArgs::Multicast(address) => format!("{address}"),
+                PrivateArgs::Unix { path, listen: true } => format!("Listening on {}", path.display()),
+                PrivateArgs::Unix { path, listen: false } => format!("Connecting to {}", path.display()),
+            };
+            let private_msg = ArgDisplay {
+                name: Cow::Borrowed("Private Network"),
+                value: Cow::Owned(value),
+            };
+            vec![network_msg, private_msg]
         } else {
             vec![network_msg]
         }
     }
     fn qemu_args(&self) -> impl IntoIterator<Item = QemuArg> {
-        match &self.network_type {
-            NetworkType::None => vec![arg!("-nic"), arg!("none")],
-            NetworkType::Bridged { bridge, mac_addr } => {
-                let mut nic = format!("bridge,br={}", bridge.as_ref());
-                if let Some(mac_addr) = mac_addr {
-                    nic.push_str(&format!(",mac={mac_addr}"));
+        let mut net = match &self.interface.network_type {
+            NetworkType::None => return vec![],
+            NetworkType::Bridged { bridge } => OsString::from(format!("bridge,id={},br={}", self.id, bridge.as_ref())),
+            NetworkType::Tap { tap, .. } => OsString::from(format!("tap,id={},ifname={tap},script=no,downscript=no", self.id)),
+            NetworkType::Nat {
+                ssh_port,
+                port_forwards,
+                restrict,
+                settings,
+            } => {
+                let mut net = OsString::from(format!("user,id={},hostname=", self.id));
+                net.push(settings.hostname.as_deref().unwrap_or(self.vm_name));
+                net.push(settings.qemu_options());
+                if let Some(tftp) = &settings.tftp {
+                    net.push(",tftp=");
+                    net.push(self.vm_dir.join(tftp));
                 }
-                vec![arg!("-nic"), oarg!(nic)]
-            }
-            NetworkType::Nat { ssh_port, port_forwards, restrict } => {
-                let mut net = OsString::from("user,id=nic,hostname=");
-                net.push(self.vm_name);
                 if let Some(ssh_port) = ssh_port.as_ref() {
                     net.push(",hostfwd=tcp::");
                     net.push(ssh_port.to_string());
@@ -127,56 +480,56 @@ impl EmulatorArgs for NetworkArgs<'_> {
                 if *restrict {
                     net.push(",restrict=y");
                 }
-                if let Some(samba) = self.samba {
-                    net.push(",smb=");
-                    net.push(samba);
+                // QEMU has no syntax for ranges, so each port is forwarded separately
+                for forward in port_forwards {
+                    let host_addr = forward.host_addr.map(|addr| addr.to_string()).unwrap_or_default();
+                    let guest_addr = forward.guest_addr.map(|addr| addr.to_string()).unwrap_or_default();
+                    for (host, guest) in forward.ports() {
+                        net.push(format!(",hostfwd={}:{host_addr}:{host}-{guest_addr}:{guest}", forward.protocol));
+                    }
                 }
-                for PortForward { host, guest } in port_forwards {
-                    net.push(",hostfwd=tcp::");
-                    net.push(host.to_string());
-                    net.push("-:");
-                    net.push(guest.to_string());
+                net
+            }
+            NetworkType::Private { .. } => match self.private.as_ref().expect("Private networks should have arguments") {
+                PrivateArgs::Multicast(address) => OsString::from(format!("socket,id={},mcast={address},localaddr=127.0.0.1", self.id)),
+                PrivateArgs::Unix { path, listen } => {
+                    let mut net = OsString::from(format!(
+                        "stream,id={},server={},addr.type=unix,addr.path=",
+                        self.id,
+                        if *listen { "on" } else { "off" }
+                    ));
+                    net.push(path);
+                    net
                 }
-                if let Some(samba) = self.samba {
-                    net.push(",smb=");
-                    net.push(samba);
+            },
+        };
+        if let Some(samba) = self.samba {
+            net.push(",smb=");
+            net.push(samba);
+            if let NetworkType::Nat { settings, .. } = &self.interface.network_type {
+                if settings.cidr.is_some() {
+                    net.push(format!(",smbserver={}", settings.samba_addr()));
                 }
-
-                vec![arg!("-netdev"), oarg!(net), arg!("-device"), arg!(self.network_device.arg())]
             }
         }
-    }
-}
-
-#[derive(derive_more::Display)]
-enum NetDevice {
-    E1000,
-    VMXNET3,
-    #[display("VirtIO Net")]
-    VirtIONet,
-    #[display("RTL 8139")]
-    RTL8139,
-}
 
-impl NetDevice {
-    fn arg(&self) -> &'static str {
-        match self {
-            Self::E1000 => "e1000,netdev=nic",
-            Self::VMXNET3 => "vmxnet3,netdev=nic",
-            Self::VirtIONet => "virtio-net,netdev=nic",
-            Self::RTL8139 => "rtl8139,netdev=nic",
+        let mut device = format!("{},netdev={}", self.network_device.model(), self.id);
+        if let Some(mac_addr) = &self.mac_addr {
+            device.push_str(&format!(",mac={mac_addr}"));
         }
+        vec![arg!("-netdev"), oarg!(net), arg!("-device"), oarg!(device)]
     }
-}
-
-impl From<GuestOS> for NetDevice {
-    fn from(guest: GuestOS) -> Self {
-        match guest {
-            GuestOS::ReactOS => Self::E1000,
-            GuestOS::MacOS { release } if release >= MacOSRelease::BigSur => Self::VirtIONet,
-            GuestOS::MacOS { .. } => Self::VMXNET3,
-            GuestOS::Linux | GuestOS::LinuxOld | GuestOS::Solaris | GuestOS::GhostBSD | GuestOS::FreeBSD | GuestOS::GenericBSD => Self::VirtIONet,
-            _ => Self::RTL8139,
-        }
+    fn launch_fns(self) -> impl IntoIterator<Item = LaunchFn> {
+        let Some(PrivateArgs::Unix { path, listen: true }) = self.private else { return None };
+        Some(LaunchFn::Before(Box::new(move || {
+            let dir = path.parent().expect("Socket should have a parent directory");
+            std::fs::create_dir_all(dir).map_err(|e| Error::RuntimeDir(dir.display().to_string(), e.to_string()))?;
+            // A socket left behind by a VM which didn't exit cleanly would stop QEMU from listening
+            #[cfg(unix)]
+            if path.exists() && std::os::unix::net::UnixStream::connect(&path).is_err() {
+                std::fs::remove_file(&path).map_err(|e| Error::StaleSocket(path.display().to_string(), e.to_string()))?;
+            }
+            Ok(Vec::new())
+        })))
     }
 }
@@ -3,20 +3,36 @@ use std::{
     ffi::OsString,
     io::{Read, Write},
     net::TcpStream,
+    path::Path,
+    time::Duration,
 };
 
 use crate::{
     arg,
     data::{Monitor, MonitorArg, MonitorInner},
     error::{Error, MonitorError},
+    oarg,
+    qmp::{HumanMonitorCommand, QmpClient},
     utils::{find_port, ArgDisplay, EmulatorArgs, QemuArg},
 };
 
 #[cfg(unix)]
 use std::os::unix::net::UnixStream;
 
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
 impl Monitor {
     pub fn send_cmd(&self, command: &str) -> Result<String, MonitorError> {
+        self.send_cmd_inner(command, None)
+    }
+
+    // Sends a command, waiting up to the specified time for a response. Used for long-running commands
+    pub fn send_cmd_timeout(&self, command: &str, timeout: Duration) -> Result<String, MonitorError> {
+        self.send_cmd_inner(command, Some(timeout))
+    }
+
+    fn send_cmd_inner(&self, command: &str, timeout: Option<Duration>) -> Result<String, MonitorError> {
+        let read_timeout = timeout.unwrap_or(DEFAULT_TIMEOUT);
         let mut response = String::new();
         match self {
             MonitorInner::Telnet { address } => {
@@ -24,9 +40,7 @@ impl Monitor {
                 stream.write_all(command.as_bytes()).map_err(MonitorError::Write)?;
                 stream.write_all(b"\r\n").map_err(MonitorError::Write)?;
                 stream.shutdown(std::net::Shutdown::Write).map_err(MonitorError::Write)?;
-                stream
-                    .set_read_timeout(Some(std::time::Duration::from_secs(1)))
-                    .map_err(MonitorError::Read)?;
+                stream.set_read_timeout(Some(read_timeout)).map_err(MonitorError::Read)?;
                 stream.read_to_string(&mut response).map_err(MonitorError::Read)?;
             }
             #[cfg(unix)]
@@ -35,20 +49,50 @@ impl Monitor {
                 stream.write_all(command.as_bytes()).map_err(MonitorError::Write)?;
                 stream.write_all(b"\r\n").map_err(MonitorError::Write)?;
                 stream.shutdown(std::net::Shutdown::Write).map_err(MonitorError::Write)?;
-                stream
-                    .set_read_timeout(Some(std::time::Duration::from_secs(1)))
-                    .map_err(MonitorError::Read)?;
+                stream.set_read_timeout(Some(read_timeout)).map_err(MonitorError::Read)?;
                 stream.read_to_string(&mut response).map_err(MonitorError::Read)?;
             }
+            MonitorInner::Qmp { .. } => {
+                let mut client = self.qmp()?;
+                if timeout.is_some() {
+                    client.set_timeout(timeout);
+                }
+                response = client.execute(&HumanMonitorCommand { command_line: command })?;
+            }
             _ => return Err(MonitorError::NoMonitor),
         }
         Ok(response)
     }
+
+    pub fn qmp(&self) -> Result<QmpClient, MonitorError> {
+        match self {
+            MonitorInner::Qmp { address: Some(address), .. } => QmpClient::connect_tcp(address.as_ref()),
+            #[cfg(unix)]
+            MonitorInner::Qmp { socketpath: Some(ref socketpath), .. } => QmpClient::connect_unix(socketpath),
+            MonitorInner::Qmp { .. } => Err(MonitorError::NoMonitor),
+            _ => Err(MonitorError::NoQmp),
+        }
+    }
 }
 
 impl<T: MonitorArg> MonitorInner<T> {
+    // The Unix socket QEMU creates for this monitor, if any
+    #[cfg(unix)]
+    pub(crate) fn socketpath(&self) -> Option<&Path> {
+        match self {
+            Self::Socket { socketpath } | Self::Qmp { socketpath, .. } => socketpath.as_deref(),
+            _ => None,
+        }
+    }
+
     pub(crate) fn validate(&mut self) -> Result<(), Error> {
-        if let Self::Telnet { address } = self {
+        match self {
+            Self::Qmp { .. } if !T::supports_qmp() => return Err(Error::UnsupportedQmp(T::display())),
+            #[cfg(not(unix))]
+            Self::Qmp { address: address @ None } => *address = Some(T::default()),
+            _ => {}
+        }
+        if let Self::Telnet { address } | Self::Qmp { address: Some(address), .. } = self {
             let defined_port = address.as_ref().port();
             let port = find_port(defined_port, 9).ok_or(Error::UnavailablePort(defined_port))?;
             address.as_mut().set_port(port);
@@ -67,6 +111,14 @@ impl<T: MonitorArg> EmulatorArgs for MonitorInner<T> {
                 "socket {}",
                 socketpath.as_ref().expect("Socketpath should be filled").display()
             )),
+            Self::Qmp { address: Some(address), .. } => Cow::Owned(format!("QMP tcp {}", address.as_ref())),
+            #[cfg(unix)]
+            Self::Qmp { socketpath, .. } => Cow::Owned(format!(
+                "QMP socket {}",
+                socketpath.as_ref().expect("Socketpath should be filled").display()
+            )),
+            #[cfg(not(unix))]
+            Self::Qmp { .. } => unreachable!("QMP address should be filled"),
         };
         Some(ArgDisplay {
             name: Cow::Borrowed(T::display()),
@@ -89,8 +141,24 @@ impl<T: MonitorArg> EmulatorArgs for MonitorInner<T> {
                 socket.push(",server,nowait");
                 Cow::Owned(socket)
             }
+            Self::Qmp { address: Some(address), .. } => {
+                let mut qmp = OsString::from("tcp:");
+                qmp.push(address.as_ref().to_string());
+                qmp.push(",server,nowait");
+                // The human monitor is explicitly disabled, otherwise QEMU would attach it to a virtual console
+                return vec![arg!(T::arg()), arg!("none"), arg!("-qmp"), oarg!(qmp)];
+            }
+            #[cfg(unix)]
+            Self::Qmp { socketpath, .. } => {
+                let mut qmp = OsString::from("unix:");
+                qmp.push(socketpath.as_ref().expect("Socketpath should be filled"));
+                qmp.push(",server,nowait");
+                return vec![arg!(T::arg()), arg!("none"), arg!("-qmp"), oarg!(qmp)];
+            }
+            #[cfg(not(unix))]
+            Self::Qmp { .. } => unreachable!("QMP address should be filled"),
         };
 
-        [arg!(T::arg()), arg]
+        vec![arg!(T::arg()), arg]
     }
 }
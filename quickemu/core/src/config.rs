@@ -5,24 +5,38 @@ use std::{ffi::OsString, path::PathBuf};
 #[cfg(feature = "quickemu")]
 use crate::{
     arg,
-    error::{ConfigError, Error, MonitorError, Warning},
+    args::GuestAgentArgs,
+    error::{ConfigError, Error, LiveVMError, MonitorError, Warning},
     full_qemu_args,
+    hooks::HookStage,
+    layer,
     live_vm::LiveVM,
-    oarg, qemu_args,
+    oarg,
+    overrides::Overrides,
+    plan::{LaunchPlan, PlannedAction},
+    qmp::QmpClient,
+    report::{absolute, LaunchReport},
+    supervisor::{RunningVM, SpawnGuard, Supervisor},
     utils::{ArgDisplay, EmulatorArgs, LaunchFn, LaunchFnReturn, QemuArg},
 };
 #[cfg(feature = "quickemu")]
 use std::{
     borrow::Cow,
+    fs::OpenOptions,
     path::Path,
-    process::{Child, Command},
-    thread::JoinHandle,
+    process::{Command, Stdio},
 };
 #[cfg(feature = "quickemu")]
 use which::which;
 
-#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+// The current version of the configuration schema. Older configurations are migrated when read
+pub const CONFIG_VERSION: u32 = 2;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
+    // Configurations written before versioning was introduced are treated as version 1
+    #[serde(default = "unversioned")]
+    pub version: u32,
     #[serde(default, skip_serializing_if = "is_default")]
     pub vm_dir: Option<PathBuf>,
     #[serde(default, skip_serializing_if = "is_default")]
@@ -36,10 +50,57 @@ pub struct Config {
     pub network: Network,
     #[serde(default, skip_serializing_if = "is_default")]
     pub io: Io,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub restart: Restart,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub hooks: Hooks,
+    // Adds a virtio-serial channel for the QEMU guest agent (qemu-ga), reachable through a socket in the VM directory
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub guest_agent: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "os_strings")]
     pub extra_args: Vec<OsString>,
 }
 
+// Arguments are written as plain strings, rather than serde's platform-specific representation of OS strings
+mod os_strings {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::ffi::OsString;
+
+    pub fn serialize<S: Serializer>(args: &[OsString], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(args.iter().map(|arg| arg.to_string_lossy()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<OsString>, D::Error> {
+        Ok(Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(OsString::from)
+            .collect())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            vm_dir: None,
+            vm_name: String::new(),
+            guest: GuestOS::default(),
+            machine: Machine::default(),
+            images: Images::default(),
+            network: Network::default(),
+            io: Io::default(),
+            restart: Restart::default(),
+            hooks: Hooks::default(),
+            guest_agent: false,
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+const fn unversioned() -> u32 {
+    1
+}
+
 #[cfg(feature = "quickemu")]
 #[derive(Debug)]
 pub struct QemuArgs {
@@ -48,6 +109,8 @@ pub struct QemuArgs {
     pub after_launch_fns: Vec<LaunchFn>,
     pub before_launch_fns: Vec<LaunchFn>,
     pub display: Vec<ArgDisplay>,
+    // Side effects of launching, which are skipped and only reported in dry runs
+    pub planned_actions: Vec<PlannedAction>,
 }
 
 #[cfg(feature = "quickemu")]
@@ -55,8 +118,8 @@ pub struct QemuArgs {
 pub struct LaunchResult {
     pub display: Vec<ArgDisplay>,
     pub warnings: Vec<Warning>,
-    pub threads: Vec<JoinHandle<Result<(), Error>>>,
-    pub children: Vec<Child>,
+    pub report: LaunchReport,
+    pub supervisor: Supervisor,
 }
 
 #[cfg(feature = "quickemu")]
@@ -69,8 +132,30 @@ pub enum ParsedVM {
 #[cfg(feature = "quickemu")]
 impl<'a> Config {
     pub fn parse(file: &Path) -> Result<ParsedVM, ConfigError> {
+        Self::parse_with_overrides(file, &Overrides::default())
+    }
+
+    pub fn parse_with_overrides(file: &Path, overrides: &Overrides) -> Result<ParsedVM, ConfigError> {
+        let conf = Self::read_with_overrides(file, overrides)?;
+        Ok(if let Some(live_vm) = conf.live_vm()? { ParsedVM::Live(live_vm) } else { ParsedVM::Config(conf) })
+    }
+
+    pub fn read(file: &Path) -> Result<Self, ConfigError> {
+        Self::read_with_overrides(file, &Overrides::default())
+    }
+
+    pub fn read_with_overrides(file: &Path, overrides: &Overrides) -> Result<Self, ConfigError> {
         let contents = std::fs::read_to_string(file)?;
-        let mut conf: Self = toml::from_str(&contents).map_err(ConfigError::Parse)?;
+        Self::from_toml(&contents, file, overrides)
+    }
+
+    pub(crate) fn from_toml(contents: &str, file: &Path, overrides: &Overrides) -> Result<Self, ConfigError> {
+        let mut conf: Self = match layer::load(contents, file, overrides)? {
+            Some(table) => toml::Value::Table(table.clone())
+                .try_into()
+                .map_err(|err| ConfigError::ParseMerged(layer::error_key(&table), err))?,
+            None => toml::from_str(contents).map_err(ConfigError::Parse)?,
+        };
         if conf.vm_dir.is_none() {
             if conf.vm_name.is_empty() {
                 let filename = file.file_name().expect("Filename should exist").to_string_lossy();
@@ -79,14 +164,30 @@ impl<'a> Config {
             }
             conf.vm_dir = Some(file.parent().unwrap().join(&conf.vm_name));
         }
-        Ok(if let Some(live_vm) = LiveVM::find_active(conf.vm_dir.as_ref().unwrap())? {
-            ParsedVM::Live(live_vm)
-        } else {
-            ParsedVM::Config(conf)
-        })
+        Ok(conf)
+    }
+
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string_pretty(self)
+    }
+
+    pub fn live_vm(&self) -> Result<Option<LiveVM>, LiveVMError> {
+        LiveVM::find_active(self.vm_dir.as_ref().expect("VM directory should be set"))
     }
 
     fn finalize(&mut self) -> Result<(), Error> {
+        self.network.monitor.validate()?;
+        self.network.serial.validate()?;
+        self.network.validate()?;
+        self.fill_defaults();
+        self.network.assign_ssh_ports();
+        #[cfg(not(target_os = "macos"))]
+        self.io.display.assign_spice_port()?;
+        Ok(())
+    }
+
+    // Fill in the VM name and socket paths, which QEMU is given unless they're set explicitly
+    pub(crate) fn fill_defaults(&mut self) {
         if self.vm_name.is_empty() {
             self.vm_name = self
                 .vm_dir
@@ -97,8 +198,6 @@ impl<'a> Config {
                 .to_string_lossy()
                 .to_string();
         }
-        self.network.monitor.validate()?;
-        self.network.serial.validate()?;
         #[cfg(unix)]
         {
             if let MonitorInner::Socket { socketpath } = &mut self.network.monitor {
@@ -106,149 +205,333 @@ impl<'a> Config {
                     *socketpath = Some(self.vm_dir.as_ref().unwrap().join(format!("{}-monitor.socket", self.vm_name)));
                 }
             }
+            if let MonitorInner::Qmp { address: None, socketpath } = &mut self.network.monitor {
+                if socketpath.is_none() {
+                    *socketpath = Some(self.vm_dir.as_ref().unwrap().join(format!("{}-qmp.socket", self.vm_name)));
+                }
+            }
             if let MonitorInner::Socket { socketpath } = &mut self.network.serial {
                 if socketpath.is_none() {
                     *socketpath = Some(self.vm_dir.as_ref().unwrap().join(format!("{}-serial.socket", self.vm_name)));
                 }
             }
         }
-        Ok(())
     }
 
     pub fn send_monitor_command(&self, command: &str) -> Result<String, MonitorError> {
         self.network.monitor.send_cmd(command)
     }
 
+    pub fn qmp(&self) -> Result<QmpClient, MonitorError> {
+        self.network.monitor.qmp()
+    }
+
     fn create_live_vm(&self) -> (LiveVM, PathBuf) {
         let vm_dir = self.vm_dir.as_ref().unwrap();
-        let ssh_port = if let NetworkType::Nat { ssh_port, .. } = &self.network.network_type {
-            *ssh_port.as_ref()
-        } else {
-            None
-        };
+        let ssh_port = self.network.ssh_port();
         #[cfg(not(target_os = "macos"))]
         let spice_port = if let DisplayType::Spice { spice_port, .. } = self.io.display.display_type {
             Some(spice_port)
         } else {
             None
         };
-        LiveVM::new(
+        let (mut live_vm, live_vm_file) = LiveVM::new(
             vm_dir,
+            self.process_name(),
             ssh_port,
             #[cfg(not(target_os = "macos"))]
             spice_port,
             self.network.monitor.clone(),
             self.network.serial.clone(),
-        )
+            self.disk_paths(),
+        );
+        live_vm.guest_agent = self.guest_agent_socket();
+        (live_vm, live_vm_file)
+    }
+
+    // Describe a QEMU process started from this configuration outside of quickemu, e.g. by a systemd unit, so that it can be managed like a launched VM.
+    // Ports are not re-validated, as the running VM is expected to hold them
+    pub fn attach(mut self, pid: u32) -> LiveVM {
+        self.fill_defaults();
+        let (live_vm, _) = self.create_live_vm();
+        live_vm.attach(pid)
+    }
+
+    pub(crate) fn disk_paths(&self) -> Vec<PathBuf> {
+        let vm_dir = self.vm_dir.as_ref().unwrap();
+        self.images.disk.iter().map(|disk| vm_dir.join(&disk.path)).collect()
     }
 
     pub fn launch(self) -> Result<LaunchResult, Error> {
-        let (live_vm, live_vm_file) = self.create_live_vm();
-        let qemu_bin_str = match self.machine.arch {
-            Arch::X86_64 { .. } => "qemu-system-x86_64",
-            Arch::AArch64 { .. } => "qemu-system-aarch64",
-            Arch::Riscv64 { .. } => "qemu-system-riscv64",
+        self.launch_inner(false)
+    }
+
+    // Launch QEMU in its own session, with its output written to the VM's log file rather than the terminal
+    pub fn launch_detached(self) -> Result<LaunchResult, Error> {
+        self.launch_inner(true)
+    }
+
+    // QEMU's output is written here when the VM is launched detached
+    pub fn log_file(&self) -> PathBuf {
+        let vm_dir = self.vm_dir.as_ref().expect("VM directory should be set");
+        let vm_name = match self.vm_name.is_empty() {
+            true => vm_dir.file_name().expect("Filename should exist").to_string_lossy(),
+            false => Cow::Borrowed(self.vm_name.as_str()),
+        };
+        vm_dir.join(format!("{vm_name}.log"))
+    }
+
+    // Where QEMU listens for connections to the guest agent, when it's enabled
+    pub fn guest_agent_socket(&self) -> Option<PathBuf> {
+        let vm_dir = self.vm_dir.as_ref().expect("VM directory should be set");
+        let vm_name = match self.vm_name.is_empty() {
+            true => vm_dir.file_name().expect("Filename should exist").to_string_lossy(),
+            false => Cow::Borrowed(self.vm_name.as_str()),
         };
+        self.guest_agent.then(|| vm_dir.join(format!("{vm_name}-agent.socket")))
+    }
+
+    fn launch_inner(self, detach: bool) -> Result<LaunchResult, Error> {
+        let config = self.clone();
+        let (mut vm, display, mut warnings) = self.spawn(detach, 0)?;
+        if detach && config.restart.policy != RestartPolicy::Never {
+            warnings.push(Warning::DetachedRestart);
+        }
+        if detach && vm.hooks.has_post_stop() {
+            warnings.push(Warning::DetachedHooks);
+        }
+        vm.report.warnings = warnings.iter().map(ToString::to_string).collect();
+        Ok(LaunchResult {
+            display,
+            warnings,
+            report: vm.report.clone(),
+            supervisor: Supervisor::new(config, vm),
+        })
+    }
+
+    // Start QEMU and its helpers. Called again by the supervisor when the VM is restarted
+    pub(crate) fn spawn(mut self, detach: bool, restarts: u32) -> Result<(RunningVM, Vec<ArgDisplay>, Vec<Warning>), Error> {
+        self.finalize()?;
+        self.network.check_port_forwards_available()?;
+        let (mut live_vm, live_vm_file) = self.create_live_vm();
+        let log_file = detach.then(|| self.log_file());
+        let state_files = self.state_files(&live_vm_file);
+        let hooks = self.hook_runner();
+        let mut hook_warnings = hooks.run(HookStage::PreStart, None, None, restarts)?;
+        let qemu_bin_str = self.machine.arch.qemu_binary();
         let qemu_bin = which(qemu_bin_str).map_err(|_| Error::QemuNotFound(qemu_bin_str))?;
-        let mut qemu_args = self.to_full_qemu_args()?;
+        let (mut qemu_args, mut report) = self.finalized_qemu_args(false)?;
 
-        let mut threads = Vec::new();
-        let mut children = Vec::new();
+        // Anything started from here on is cleaned up if the launch fails
+        let mut guard = SpawnGuard::new(state_files);
 
         for launch_fn in qemu_args.before_launch_fns {
             for launch_fn_return in launch_fn.call()? {
                 match launch_fn_return {
                     LaunchFnReturn::Arg(arg) => qemu_args.qemu_args.push(arg),
                     LaunchFnReturn::Display(display) => qemu_args.display.push(display),
-                    LaunchFnReturn::Thread(thread) => threads.push(thread),
-                    LaunchFnReturn::Process(child) => children.push(child),
+                    LaunchFnReturn::Thread(thread) => guard.threads.push(thread),
+                    LaunchFnReturn::Process(child) => guard.helpers.push(child),
                 }
             }
         }
 
         log::debug!("Launching QEMU with args {:#?}", qemu_args.qemu_args);
 
-        let qemu_process = Command::new(qemu_bin)
-            .args(qemu_args.qemu_args)
-            .spawn()
-            .map_err(|e| Error::Command(qemu_bin_str, e.to_string()))?;
+        let mut command = Command::new(qemu_bin);
+        command.args(qemu_args.qemu_args);
+        if let Some(log_file) = log_file {
+            detach_command(&mut command, &log_file)?;
+            qemu_args.display.push(ArgDisplay {
+                name: Cow::Borrowed("Log"),
+                value: Cow::Owned(log_file.display().to_string()),
+            });
+            live_vm.log_file = Some(log_file);
+        } else {
+            // Keep QEMU out of the terminal's process group, so that Ctrl+C reaches only the supervisor, which shuts the VM down cleanly
+            #[cfg(unix)]
+            std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        }
+        live_vm.restarts = restarts;
+        let qemu_process = command.spawn().map_err(|e| Error::Command(qemu_bin_str, e.to_string()))?;
+        let pid = qemu_process.id();
+        guard.qemu = Some(qemu_process);
 
-        live_vm.serialize(&live_vm_file, qemu_process.id())?;
+        live_vm.save(&live_vm_file, pid)?;
 
         qemu_args.display.push(ArgDisplay {
             name: Cow::Borrowed("PID"),
-            value: Cow::Owned(qemu_process.id().to_string()),
+            value: Cow::Owned(pid.to_string()),
         });
 
-        children.push(qemu_process);
-
         for launch_fn in qemu_args.after_launch_fns {
             for launch_fn_return in launch_fn.call()? {
                 match launch_fn_return {
                     LaunchFnReturn::Arg(_) => panic!("Arguments should not be returned in 'after' launch fns"),
                     LaunchFnReturn::Display(display) => qemu_args.display.push(display),
-                    LaunchFnReturn::Thread(thread) => threads.push(thread),
-                    LaunchFnReturn::Process(child) => children.push(child),
+                    LaunchFnReturn::Thread(thread) => guard.threads.push(thread),
+                    LaunchFnReturn::Process(child) => guard.helpers.push(child),
                 }
             }
         }
 
-        Ok(LaunchResult {
-            display: qemu_args.display,
-            warnings: qemu_args.warnings,
-            threads,
-            children,
-        })
+        hook_warnings.extend(hooks.run(HookStage::PostStart, Some(pid), None, restarts)?);
+        qemu_args.warnings.extend(hook_warnings);
+
+        report.pid = pid;
+        report.log_file = live_vm.log_file.as_deref().map(absolute);
+        report.restarts = restarts;
+        report.warnings = qemu_args.warnings.iter().map(ToString::to_string).collect();
+
+        let vm = guard.into_running(live_vm, hooks, report);
+        Ok((vm, qemu_args.display, qemu_args.warnings))
+    }
+
+    // Files which only exist while the VM is running, and are removed by its supervisor once it exits
+    fn state_files(&self, live_vm_file: &Path) -> Vec<PathBuf> {
+        let mut files = vec![live_vm_file.to_path_buf()];
+        files.extend(self.vm_state_files());
+        files
+    }
+
+    // Files in the VM directory which belong to this VM alone: its disks, along with its logs, pidfile and sockets when they're
+    // named after the VM. Unlike the live VM file, EFI variables and TPM state, removing them doesn't affect other VMs sharing the directory
+    pub fn vm_files(&self) -> Vec<PathBuf> {
+        let mut config = self.clone();
+        config.fill_defaults();
+        let vm_dir = config.vm_dir.as_ref().expect("VM directory should be set");
+        let mut files = config.disk_paths();
+        // Without a name of its own, the VM takes the directory's name, as would any other VM in it
+        if !self.vm_name.is_empty() {
+            files.extend([config.log_file(), config.console_log_file()]);
+            files.extend(config.vm_state_files());
+        }
+        files.retain(|file| file.starts_with(vm_dir));
+        files
+    }
+
+    fn vm_state_files(&self) -> Vec<PathBuf> {
+        let vm_dir = self.vm_dir.as_ref().unwrap();
+        let mut files = vec![self.pid_file()];
+        #[cfg(unix)]
+        files.extend(
+            [self.network.monitor.socketpath(), self.network.serial.socketpath()]
+                .into_iter()
+                .flatten()
+                .map(Path::to_path_buf),
+        );
+        files.extend(self.machine.tpm_socket(vm_dir, &self.vm_name));
+        files.extend(self.guest_agent_socket());
+        files
+    }
+
+    fn pid_file(&self) -> PathBuf {
+        self.vm_dir.as_ref().unwrap().join(format!("{}.pid", self.vm_name))
     }
 
     pub fn to_full_qemu_args(mut self) -> Result<QemuArgs, Error> {
         self.finalize()?;
+        self.finalized_qemu_args(false).map(|(args, _)| args)
+    }
+
+    // Build the full set of arguments without creating disks, copying firmware or starting helpers
+    pub fn plan(mut self) -> Result<LaunchPlan, Error> {
+        self.finalize()?;
+        let qemu_bin = self.machine.arch.qemu_binary();
+        let (qemu_args, _) = self.finalized_qemu_args(true)?;
+        Ok(LaunchPlan { qemu_bin, qemu_args })
+    }
+
+    // Also describes the VM, including the images attached to it, for the launch report
+    fn finalized_qemu_args(self, dry_run: bool) -> Result<(QemuArgs, LaunchReport), Error> {
         let vm_dir = self.vm_dir.as_ref().unwrap();
         #[cfg(target_arch = "x86_64")]
         self.guest.validate_cpu()?;
 
+        let mut report = self.launch_report();
+        let images = self.images.args(
+            self.guest,
+            vm_dir,
+            self.machine.status_quo,
+            self.network.monitor.clone(),
+            dry_run,
+        )?;
+        for drive in images.0.drives() {
+            match drive.cdrom {
+                true => report.isos.push(absolute(drive.path)),
+                false => report.disks.push(absolute(drive.path)),
+            }
+        }
+
         let mut args = full_qemu_args!(
             self.basic_args(),
-            self.machine.args(self.guest, vm_dir, &self.vm_name),
+            self.machine.args(self.guest, vm_dir, &self.vm_name, dry_run),
             self.io.args(self.machine.arch, self.guest, &self.vm_name),
-            self.network.args(self.guest, &self.vm_name, self.io.public_dir()),
-            self.images
-                .args(self.guest, vm_dir, self.machine.status_quo, self.network.monitor),
+            self.network
+                .args(self.guest, vm_dir, &self.vm_name, self.io.public_dir(), dry_run),
+            self.guest_agent_args(),
+            Ok::<_, Error>(images),
         )?;
 
         args.qemu_args.extend(self.extra_args.into_iter().map(|arg| oarg!(arg)));
-        Ok(args)
+        Ok((args, report))
     }
 
+    // The arguments QEMU would be launched with. Like `plan`, this has no side effects: disks aren't created,
+    // firmware isn't copied and nothing is probed beyond what finalizing the configuration does
     pub fn to_qemu_args(mut self) -> Result<(Vec<QemuArg>, Vec<Warning>), Error> {
         self.finalize()?;
-        let vm_dir = self.vm_dir.as_ref().unwrap();
-        #[cfg(target_arch = "x86_64")]
-        self.guest.validate_cpu()?;
-
-        let (mut args, warnings) = qemu_args!(
-            self.basic_args(),
-            self.machine.args(self.guest, vm_dir, &self.vm_name),
-            self.io.args(self.machine.arch, self.guest, &self.vm_name),
-            self.network.args(self.guest, &self.vm_name, self.io.public_dir()),
-            self.images
-                .args(self.guest, vm_dir, self.machine.status_quo, self.network.monitor),
-        )?;
-        args.extend(self.extra_args.into_iter().map(|arg| oarg!(arg)));
+        self.finalized_qemu_args(true).map(|(args, _)| (args.qemu_args, args.warnings))
+    }
 
-        Ok((args, warnings))
+    // The name QEMU's process will have, as set through `-name process=` on Linux
+    fn process_name(&self) -> &str {
+        if cfg!(target_os = "linux") {
+            &self.vm_name
+        } else {
+            self.machine.arch.qemu_binary()
+        }
     }
 
     fn basic_args(&'a self) -> Result<(BasicArgs<'a>, Option<Warning>), Error> {
         Ok((
             BasicArgs {
                 slew_driftfix: matches!(self.machine.arch, Arch::X86_64 { .. }),
-                pid_path: self.vm_dir.as_ref().unwrap().join(format!("{}.pid", self.vm_name)),
+                pid_path: self.pid_file(),
                 vm_name: &self.vm_name,
             },
             None,
         ))
     }
+
+    fn guest_agent_args(&self) -> Result<(GuestAgentArgs, Option<Warning>), Error> {
+        Ok((GuestAgentArgs::new(self.guest_agent_socket().as_deref()), None))
+    }
+}
+
+#[cfg(feature = "quickemu")]
+fn detach_command(command: &mut Command, log_file: &Path) -> Result<(), Error> {
+    let log_error = |e: std::io::Error| Error::LogFile(log_file.display().to_string(), e.to_string());
+    let stdout = OpenOptions::new().create(true).append(true).open(log_file).map_err(log_error)?;
+    let stderr = stdout.try_clone().map_err(log_error)?;
+    command.stdin(Stdio::null()).stdout(stdout).stderr(stderr);
+
+    // Start a new session, so that QEMU is no longer tied to the terminal it was launched from
+    #[cfg(unix)]
+    unsafe {
+        use std::os::unix::process::CommandExt;
+        command.pre_exec(|| match libc::setsid() {
+            -1 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        });
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x00000008;
+        command.creation_flags(DETACHED_PROCESS);
+    }
+    Ok(())
 }
 
 #[cfg(feature = "quickemu")]
@@ -0,0 +1,354 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, time::Duration};
+
+use crate::{config::Config, data::MonitorInner, error::ConsoleError, live_vm::LiveVM};
+
+// Ctrl+], as used by telnet and `virsh console`
+pub const DEFAULT_ESCAPE: u8 = 0x1d;
+#[cfg(unix)]
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+pub struct ConsoleOptions {
+    // Typing this byte detaches from the console
+    pub escape: u8,
+    // Console output is appended to this file, along with being written to the terminal
+    pub log_file: Option<PathBuf>,
+}
+
+impl Default for ConsoleOptions {
+    fn default() -> Self {
+        Self {
+            escape: DEFAULT_ESCAPE,
+            log_file: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleExit {
+    // The escape character was typed, or input ended
+    Detached,
+    // The VM closed the serial port, usually because it exited
+    Closed,
+}
+
+impl Config {
+    pub fn console_log_file(&self) -> PathBuf {
+        let vm_dir = self.vm_dir.as_ref().expect("VM directory should be set");
+        let vm_name = match self.vm_name.is_empty() {
+            true => vm_dir.file_name().expect("Filename should exist").to_string_lossy().to_string(),
+            false => self.vm_name.clone(),
+        };
+        vm_dir.join(format!("{vm_name}-console.log"))
+    }
+}
+
+impl LiveVM {
+    // Bridge the VM's serial port to this process's terminal until the escape character is typed or the VM closes the port.
+    // The terminal is switched to raw mode while attached, so that keys such as Ctrl+C reach the guest
+    pub fn console(&self, options: &ConsoleOptions) -> Result<ConsoleExit, ConsoleError> {
+        let (connection, telnet) = match &self.serial {
+            MonitorInner::Telnet { address } => (
+                Connection::Tcp(TcpStream::connect(address.as_ref()).map_err(ConsoleError::Connect)?),
+                true,
+            ),
+            #[cfg(unix)]
+            MonitorInner::Socket { socketpath: Some(socketpath) } => (
+                Connection::Unix(UnixStream::connect(socketpath).map_err(ConsoleError::Connect)?),
+                false,
+            ),
+            _ => return Err(ConsoleError::NoSerial),
+        };
+        let log = options
+            .log_file
+            .as_ref()
+            .map(|path| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| ConsoleError::LogFile(path.clone(), e))
+            })
+            .transpose()?;
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let output = {
+            let connection = connection.try_clone().map_err(ConsoleError::Connect)?;
+            let closed = closed.clone();
+            thread::spawn(move || {
+                let result = forward_output(connection, log, telnet);
+                closed.store(true, Ordering::SeqCst);
+                result
+            })
+        };
+
+        let terminal = RawTerminal::enable().map_err(ConsoleError::Terminal)?;
+        let exit = forward_input(&connection, options.escape, telnet, &closed);
+        drop(terminal);
+
+        // Unblocks the output thread, which is still reading from the serial port when detaching
+        let _ = connection.shutdown();
+        let output = output.join().unwrap_or(Ok(()));
+        let exit = exit?;
+        output?;
+        Ok(exit)
+    }
+}
+
+fn forward_input(mut connection: &Connection, escape: u8, telnet: bool, closed: &AtomicBool) -> Result<ConsoleExit, ConsoleError> {
+    let mut buf = [0; 1024];
+    loop {
+        if closed.load(Ordering::SeqCst) {
+            return Ok(ConsoleExit::Closed);
+        }
+        if !stdin_readable().map_err(ConsoleError::Io)? {
+            continue;
+        }
+        let len = match read_stdin(&mut buf) {
+            Ok(0) => return Ok(ConsoleExit::Detached),
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(ConsoleError::Io(e)),
+        };
+        let (input, detach) = match buf[..len].iter().position(|byte| *byte == escape) {
+            Some(position) => (&buf[..position], true),
+            None => (&buf[..len], false),
+        };
+        let written = match telnet {
+            // 0xFF starts a command in telnet, so it has to be doubled to be sent as data
+            true => {
+                let mut escaped = Vec::with_capacity(input.len());
+                for &byte in input {
+                    escaped.push(byte);
+                    if byte == IAC {
+                        escaped.push(IAC);
+                    }
+                }
+                connection.write_all(&escaped)
+            }
+            false => connection.write_all(input),
+        };
+        match written {
+            Ok(()) => {}
+            // The VM closed the port while input was being sent
+            Err(_) if closed.load(Ordering::SeqCst) => return Ok(ConsoleExit::Closed),
+            Err(e) => return Err(ConsoleError::Io(e)),
+        }
+        if detach {
+            return Ok(ConsoleExit::Detached);
+        }
+    }
+}
+
+fn forward_output(mut connection: Connection, mut log: Option<File>, telnet: bool) -> Result<(), ConsoleError> {
+    let mut stdout = io::stdout();
+    let mut filter = TelnetFilter::default();
+    let mut buf = [0; 4096];
+    loop {
+        let len = match connection.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // Shutting the connection down when detaching can surface as an error, rather than the end of the stream
+            Err(_) => return Ok(()),
+        };
+        let output = match telnet {
+            true => filter.filter(&buf[..len]),
+            false => buf[..len].to_vec(),
+        };
+        stdout
+            .write_all(&output)
+            .and_then(|_| stdout.flush())
+            .map_err(ConsoleError::Io)?;
+        if let Some(log) = &mut log {
+            log.write_all(&output).map_err(ConsoleError::Io)?;
+        }
+    }
+}
+
+// Waits briefly for input, so that the input loop notices when the VM closes the serial port
+#[cfg(unix)]
+fn stdin_readable() -> io::Result<bool> {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut fd, 1, POLL_INTERVAL.as_millis() as libc::c_int) } {
+        -1 => {
+            let err = io::Error::last_os_error();
+            match err.kind() {
+                io::ErrorKind::Interrupted => Ok(false),
+                _ => Err(err),
+            }
+        }
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+#[cfg(not(unix))]
+fn stdin_readable() -> io::Result<bool> {
+    Ok(true)
+}
+
+// Reads from the file descriptor itself; input left behind in std's buffer would not wake `stdin_readable`,
+// so anything pasted past the first read would wait for the next keypress
+#[cfg(unix)]
+fn read_stdin(buf: &mut [u8]) -> io::Result<usize> {
+    match unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) } {
+        -1 => Err(io::Error::last_os_error()),
+        len => Ok(len as usize),
+    }
+}
+
+#[cfg(not(unix))]
+fn read_stdin(buf: &mut [u8]) -> io::Result<usize> {
+    io::stdin().read(buf)
+}
+
+enum Connection {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Connection {
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Self::Tcp(stream) => Self::Tcp(stream.try_clone()?),
+            #[cfg(unix)]
+            Self::Unix(stream) => Self::Unix(stream.try_clone()?),
+        })
+    }
+    fn shutdown(&self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.shutdown(Shutdown::Both),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for &Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => (&*stream).write(buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => (&*stream).write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+const IAC: u8 = 255;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+// QEMU's telnet server negotiates options when a client connects. Those commands are removed from the output
+#[derive(Default)]
+struct TelnetFilter {
+    state: TelnetState,
+}
+
+#[derive(Default, Clone, Copy)]
+enum TelnetState {
+    #[default]
+    Data,
+    Command,
+    Option,
+    Subnegotiation,
+    SubnegotiationCommand,
+}
+
+impl TelnetFilter {
+    fn filter(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(input.len());
+        for &byte in input {
+            self.state = match (self.state, byte) {
+                (TelnetState::Data, IAC) => TelnetState::Command,
+                (TelnetState::Data, _) => {
+                    output.push(byte);
+                    TelnetState::Data
+                }
+                (TelnetState::Command, IAC) => {
+                    output.push(IAC);
+                    TelnetState::Data
+                }
+                (TelnetState::Command, SB) => TelnetState::Subnegotiation,
+                // WILL, WONT, DO and DONT are followed by an option
+                (TelnetState::Command, 251..=254) => TelnetState::Option,
+                (TelnetState::Command | TelnetState::Option, _) => TelnetState::Data,
+                (TelnetState::Subnegotiation, IAC) => TelnetState::SubnegotiationCommand,
+                (TelnetState::Subnegotiation, _) => TelnetState::Subnegotiation,
+                (TelnetState::SubnegotiationCommand, SE) => TelnetState::Data,
+                (TelnetState::SubnegotiationCommand, _) => TelnetState::Subnegotiation,
+            };
+        }
+        output
+    }
+}
+
+// Puts the terminal into raw mode, restoring it when dropped. Does nothing when input isn't a terminal
+struct RawTerminal {
+    #[cfg(unix)]
+    original: Option<libc::termios>,
+}
+
+impl RawTerminal {
+    #[cfg(unix)]
+    fn enable() -> io::Result<Self> {
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } != 1 {
+            return Ok(Self { original: None });
+        }
+        let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let original = termios;
+        unsafe { libc::cfmakeraw(&mut termios) };
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { original: Some(original) })
+    }
+
+    #[cfg(not(unix))]
+    fn enable() -> io::Result<Self> {
+        Ok(Self {})
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(original) = &self.original {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original) };
+        }
+    }
+}
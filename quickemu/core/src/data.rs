@@ -1,16 +1,20 @@
 pub mod display;
 pub mod guest;
+pub mod hooks;
 pub mod image;
 pub mod io;
 pub mod machine;
 pub mod network;
+pub mod restart;
 
 pub use display::*;
 pub use guest::*;
+pub use hooks::*;
 pub use image::*;
 pub use io::*;
 pub use machine::*;
 pub use network::*;
+pub use restart::*;
 
 use serde::{de, Deserialize};
 use std::fmt;
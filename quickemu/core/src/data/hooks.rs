@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+// Shell commands run at points in the VM's lifecycle. Each is run with `sh -c` (`cmd /C` on Windows) from the VM directory
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hooks {
+    // Run before QEMU is started. A failing command aborts the launch
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_start: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_start: Vec<String>,
+    // Run once QEMU has exited, while quickemu is supervising it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_stop: Vec<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.pre_start.is_empty() && self.post_start.is_empty() && self.post_stop.is_empty()
+    }
+}
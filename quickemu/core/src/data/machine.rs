@@ -17,6 +17,8 @@ pub struct Machine {
     pub ram: Option<u64>,
     #[serde(default, skip_serializing_if = "is_default")]
     pub status_quo: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watchdog: Option<WatchdogAction>,
 }
 
 #[derive(Display, Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -50,6 +52,13 @@ impl Arch {
             Riscv64Machine::iter().map(|machine| Self::Riscv64 { machine })
         )
     }
+    pub fn qemu_binary(&self) -> &'static str {
+        match self {
+            Self::X86_64 { .. } => "qemu-system-x86_64",
+            Self::AArch64 { .. } => "qemu-system-aarch64",
+            Self::Riscv64 { .. } => "qemu-system-riscv64",
+        }
+    }
 }
 
 impl Default for Arch {
@@ -97,3 +106,24 @@ impl Default for BootType {
         Self::Efi { secure_boot: false }
     }
 }
+
+// What QEMU does when the guest stops petting the i6300esb watchdog
+#[derive(Display, Copy, Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatchdogAction {
+    #[default]
+    #[display("reset")]
+    Reset,
+    #[display("shutdown")]
+    Shutdown,
+    #[display("poweroff")]
+    Poweroff,
+    #[display("pause")]
+    Pause,
+    #[display("debug")]
+    Debug,
+    #[display("none")]
+    None,
+    #[display("inject-nmi")]
+    InjectNmi,
+}
@@ -1,5 +1,5 @@
 use std::{
-    net::{IpAddr, Ipv4Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4},
     path::PathBuf,
 };
 
@@ -8,21 +8,84 @@ use crate::utils::find_port;
 #[cfg(feature = "quickemu")]
 use serde::de::Visitor;
 
-use super::{default_if_empty, is_default};
+use super::{is_default, is_true};
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Network {
-    #[serde(default, flatten, deserialize_with = "default_if_empty")]
-    pub network_type: NetworkType,
+    // Each interface is a separate NIC in the guest. An empty list disables networking
+    #[serde(default = "default_interfaces", skip_serializing_if = "is_default_interfaces")]
+    pub interfaces: Vec<NetworkInterface>,
     #[serde(default, skip_serializing_if = "is_default")]
     pub monitor: Monitor,
     #[serde(default, skip_serializing_if = "is_default")]
     pub serial: Serial,
 }
 
+impl Default for Network {
+    fn default() -> Self {
+        Self {
+            interfaces: default_interfaces(),
+            monitor: Monitor::default(),
+            serial: Serial::default(),
+        }
+    }
+}
+
+fn default_interfaces() -> Vec<NetworkInterface> {
+    vec![NetworkInterface::default()]
+}
+
+fn is_default_interfaces(interfaces: &[NetworkInterface]) -> bool {
+    interfaces == default_interfaces()
+}
+
+impl Network {
+    // The SSH port forwarded to the first NAT interface, which is the one quickemu connects through
+    pub fn ssh_port(&self) -> Option<u16> {
+        self.interfaces.iter().find_map(|interface| match &interface.network_type {
+            NetworkType::Nat { ssh_port, .. } => Some(*ssh_port.as_ref()),
+            _ => None,
+        })?
+    }
+
+    pub fn port_forwards(&self) -> impl Iterator<Item = &PortForward> {
+        self.interfaces.iter().flat_map(|interface| match &interface.network_type {
+            NetworkType::Nat { port_forwards, .. } => port_forwards.as_slice(),
+            _ => &[],
+        })
+    }
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetworkInterface {
+    // Required, as defaulting a flattened field would discard the errors within it
+    #[serde(flatten)]
+    pub network_type: NetworkType,
+    // Overrides the NIC model selected for the guest OS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<NetDevice>,
+    // Optional. Otherwise, QEMU assigns each interface its own address
+    #[serde(default, alias = "MAC Address", alias = "macaddr", skip_serializing_if = "Option::is_none")]
+    pub mac_addr: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, derive_more::Display)]
+#[serde(rename_all = "snake_case")]
+pub enum NetDevice {
+    #[display("E1000")]
+    E1000,
+    #[display("VMXNET3")]
+    Vmxnet3,
+    #[serde(alias = "virtio-net", alias = "virtio")]
+    #[display("VirtIO Net")]
+    VirtioNet,
+    #[display("RTL 8139")]
+    Rtl8139,
+}
+
 #[cfg_attr(not(feature = "quickemu"), derive(Default))]
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, derive_more::AsRef)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, derive_more::AsRef, derive_more::AsMut)]
 pub struct SSHPort(Option<u16>);
 #[cfg(feature = "quickemu")]
 impl Default for SSHPort {
@@ -48,30 +111,133 @@ impl Visitor<'_> for SSHPort {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, derive_more::AsRef)]
 pub struct Bridge(String);
 
-#[cfg(feature = "quickemu")]
-impl Visitor<'_> for Bridge {
-    type Value = Bridge;
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct PortForward {
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub protocol: Protocol,
+    // The host address QEMU listens on. By default, it listens on every address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_addr: Option<Ipv4Addr>,
+    pub host: PortRange,
+    // By default, connections are forwarded to the guest's DHCP address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest_addr: Option<Ipv4Addr>,
+    // Either a single port, which every host port is forwarded to, or a range of the same length as the host's
+    pub guest: PortRange,
+}
+
+impl PortForward {
+    // Each host port, with the guest port it's forwarded to
+    pub fn ports(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.host.ports().enumerate().map(|(offset, host)| match self.guest.count() {
+            1 => (host, self.guest.start),
+            _ => (host, self.guest.start + offset as u16),
+        })
+    }
+}
+
+impl std::fmt::Display for PortForward {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(host_addr) = self.host_addr {
+            write!(f, "{host_addr}:")?;
+        }
+        write!(f, "{} => ", self.host)?;
+        if let Some(guest_addr) = self.guest_addr {
+            write!(f, "{guest_addr}:")?;
+        }
+        write!(f, "{}", self.guest)?;
+        if self.protocol == Protocol::Udp {
+            write!(f, " (UDP)")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, derive_more::Display)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    #[display("tcp")]
+    Tcp,
+    #[display("udp")]
+    Udp,
+}
+
+// A single port, written as a number, or an inclusive range of ports written as e.g. "8000-8010"
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn single(port: u16) -> Self {
+        Self { start: port, end: port }
+    }
+    pub fn count(&self) -> usize {
+        usize::from(self.end - self.start) + 1
+    }
+    pub fn ports(&self) -> std::ops::RangeInclusive<u16> {
+        self.start..=self.end
+    }
+}
+
+impl std::fmt::Display for PortRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.start == self.end {
+            true => write!(f, "{}", self.start),
+            false => write!(f, "{}-{}", self.start, self.end),
+        }
+    }
+}
+
+impl Serialize for PortRange {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.start == self.end {
+            true => serializer.serialize_u16(self.start),
+            false => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PortRange {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PortRangeVisitor)
+    }
+}
+
+struct PortRangeVisitor;
+impl serde::de::Visitor<'_> for PortRangeVisitor {
+    type Value = PortRange;
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("a bridge device")
+        formatter.write_str("a port number, or a range of ports such as \"8000-8010\"")
+    }
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        u16::try_from(value).map(PortRange::single).map_err(E::custom)
+    }
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        u16::try_from(value).map(PortRange::single).map_err(E::custom)
     }
     fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
-        let networks = sysinfo::Networks::new_with_refreshed_list();
-        if !networks.contains_key(value) {
-            return Err(E::custom(format!("Network interface {value} could not be found.")));
+        let (start, end) = value.split_once('-').unwrap_or((value, value));
+        let start = start.trim().parse::<u16>().map_err(E::custom)?;
+        let end = end.trim().parse::<u16>().map_err(E::custom)?;
+        if start > end {
+            return Err(E::custom(format!("port range {value} ends before it starts")));
         }
-        Ok(Self(value.to_string()))
+        Ok(PortRange { start, end })
     }
 }
 
-#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
-pub struct PortForward {
-    pub host: u16,
-    pub guest: u16,
-}
-
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
@@ -80,8 +246,14 @@ pub enum NetworkType {
     #[serde(alias = "Bridged")]
     Bridged {
         bridge: Bridge,
-        #[serde(default, alias = "MAC Address", alias = "macaddr", skip_serializing_if = "Option::is_none")]
-        mac_addr: Option<String>,
+    },
+    // An existing TAP device, which QEMU attaches to directly rather than through a helper
+    #[serde(alias = "TAP")]
+    Tap {
+        tap: String,
+        // The bridge the TAP device is added to when it's set up
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bridge: Option<String>,
     },
     #[serde(alias = "NAT")]
     Nat {
@@ -91,15 +263,121 @@ pub enum NetworkType {
         ssh_port: SSHPort,
         #[serde(default, skip_serializing_if = "is_default")]
         restrict: bool,
+        #[serde(flatten)]
+        settings: NatSettings,
+    },
+    // A network shared only with other VMs which use the same name
+    #[serde(alias = "Private")]
+    Private {
+        name: String,
+        #[serde(default, skip_serializing_if = "is_default")]
+        backend: PrivateBackend,
+        // The multicast group and port. By default, they're derived from the network's name
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        address: Option<SocketAddrV4>,
     },
 }
 
+// The virtual network QEMU's user networking creates. Unset addresses use QEMU's defaults within the network
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NatSettings {
+    // By default, 10.0.2.0/24
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cidr: Option<Cidr<Ipv4Addr>>,
+    // The host's address within the network, which the guest uses as its gateway
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_addr: Option<Ipv4Addr>,
+    // The first address given to the guest through DHCP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dhcp_start: Option<Ipv4Addr>,
+    // The address of the DNS server QEMU provides
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Ipv4Addr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns_search: Vec<String>,
+    // By default, the VM's name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(default = "default_ipv6", skip_serializing_if = "is_true")]
+    pub ipv6: bool,
+    // By default, fec0::/64
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv6_prefix: Option<Cidr<Ipv6Addr>>,
+    // A directory served to the guest over TFTP, relative to the VM directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tftp: Option<PathBuf>,
+    // The file offered to the guest for PXE boot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootfile: Option<String>,
+}
+
+fn default_ipv6() -> bool {
+    true
+}
+
+// An address and prefix length, written as e.g. "10.0.3.0/24"
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cidr<A> {
+    pub addr: A,
+    pub prefix: u8,
+}
+
+pub trait CidrAddr: Copy + std::str::FromStr + std::fmt::Display {
+    const BITS: u8;
+}
+
+impl CidrAddr for Ipv4Addr {
+    const BITS: u8 = 32;
+}
+
+impl CidrAddr for Ipv6Addr {
+    const BITS: u8 = 128;
+}
+
+impl<A: CidrAddr> std::fmt::Display for Cidr<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl<A: CidrAddr> Serialize for Cidr<A> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, A: CidrAddr> Deserialize<'de> for Cidr<A> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        let cidr = value.split_once('/').and_then(|(addr, prefix)| {
+            Some(Cidr {
+                addr: addr.parse::<A>().ok()?,
+                prefix: prefix.parse::<u8>().ok().filter(|prefix| *prefix <= A::BITS)?,
+            })
+        });
+        cidr.ok_or_else(|| serde::de::Error::custom(format!("{value} is not a valid network, e.g. \"10.0.3.0/24\"")))
+    }
+}
+
+#[derive(Default, Copy, Clone, Debug, PartialEq, Serialize, Deserialize, derive_more::Display)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivateBackend {
+    // UDP multicast on the loopback interface. Any number of VMs can join
+    #[default]
+    #[display("multicast")]
+    Multicast,
+    // A Unix stream socket in the runtime directory, connecting exactly two VMs
+    #[display("unix socket")]
+    Unix,
+}
+
 impl Default for NetworkType {
     fn default() -> Self {
         Self::Nat {
             port_forwards: vec![],
             ssh_port: SSHPort::default(),
             restrict: false,
+            settings: NatSettings::default(),
         }
     }
 }
@@ -122,11 +400,22 @@ pub enum MonitorInner<T: MonitorArg> {
     Socket {
         socketpath: Option<PathBuf>,
     },
+    #[serde(alias = "QMP")]
+    Qmp {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        address: Option<T>,
+        #[cfg(unix)]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        socketpath: Option<PathBuf>,
+    },
 }
 
 pub trait MonitorArg: Default + AsRef<SocketAddr> + AsMut<SocketAddr> {
     fn arg() -> &'static str;
     fn display() -> &'static str;
+    fn supports_qmp() -> bool {
+        false
+    }
 }
 
 #[cfg(unix)]
@@ -159,6 +448,9 @@ impl MonitorArg for MonitorAddr {
     fn display() -> &'static str {
         "Monitor"
     }
+    fn supports_qmp() -> bool {
+        true
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, derive_more::AsRef, derive_more::AsMut)]
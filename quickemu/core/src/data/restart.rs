@@ -0,0 +1,45 @@
+use super::is_default;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Restart {
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub policy: RestartPolicy,
+    // Restarts are unlimited when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+    // Seconds to wait before the first restart. The delay doubles with each restart, up to `max_backoff`
+    #[serde(default = "default_backoff")]
+    pub backoff: u64,
+    #[serde(default = "default_max_backoff")]
+    pub max_backoff: u64,
+}
+
+impl Default for Restart {
+    fn default() -> Self {
+        Self {
+            policy: RestartPolicy::default(),
+            max_retries: None,
+            backoff: default_backoff(),
+            max_backoff: default_max_backoff(),
+        }
+    }
+}
+
+const fn default_backoff() -> u64 {
+    1
+}
+const fn default_max_backoff() -> u64 {
+    300
+}
+
+#[derive(Copy, Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    // Restart when QEMU exits with a non-zero status
+    OnFailure,
+    // Restart whenever QEMU exits, unless the VM was stopped through quickemu
+    Always,
+}
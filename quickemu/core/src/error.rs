@@ -1,14 +1,28 @@
-use std::fmt;
+use std::{fmt, path::PathBuf};
 
 use size::Size;
 
-use crate::{data::GuestOS, fl};
+use crate::{data::GuestOS, fl, hooks::HookStage};
 
 #[derive(derive_more::From, Debug)]
 pub enum ConfigError {
     Read(std::io::Error),
     Parse(toml::de::Error),
+    // A configuration which was migrated or layered, and so was parsed from a merged table. Errors from it have no location,
+    // so the key which failed is found instead, when possible
+    #[from(ignore)]
+    ParseMerged(Option<String>, toml::de::Error),
     LiveVM(LiveVMError),
+    #[from(ignore)]
+    UnsupportedVersion(u32),
+    #[from(ignore)]
+    Include(PathBuf, Box<ConfigError>),
+    #[from(ignore)]
+    IncludeCycle(PathBuf),
+    #[from(ignore)]
+    InvalidOverride(String),
+    #[from(ignore)]
+    Libvirt(String),
 }
 
 impl std::error::Error for ConfigError {}
@@ -16,8 +30,17 @@ impl fmt::Display for ConfigError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let text = match self {
             Self::Read(err) => fl!("read-config-error", err = err.to_string()),
-            Self::Parse(err) => fl!("parse-config-error", err = err.to_string()),
+            Self::Parse(err) | Self::ParseMerged(_, err) => fl!("parse-config-error", err = err.to_string()),
             Self::LiveVM(err) => err.to_string(),
+            Self::UnsupportedVersion(version) => fl!(
+                "unsupported-config-version",
+                version = version,
+                current = crate::config::CONFIG_VERSION
+            ),
+            Self::Include(path, err) => fl!("include-config-error", path = path.display().to_string(), err = err.to_string()),
+            Self::IncludeCycle(path) => fl!("include-cycle", path = path.display().to_string()),
+            Self::InvalidOverride(key) => fl!("invalid-override", key = key),
+            Self::Libvirt(err) => fl!("libvirt-config-error", err = err),
         };
         f.write_str(&text)
     }
@@ -28,6 +51,8 @@ pub enum LiveVMError {
     LiveVMDe(String),
     DelLiveFile(String),
     VMKill(String),
+    MonitorCommand(String),
+    VMStillRunning(u32),
 }
 
 impl std::error::Error for LiveVMError {}
@@ -37,6 +62,8 @@ impl fmt::Display for LiveVMError {
             Self::LiveVMDe(err) => fl!("failed-live-vm-de", err = err),
             Self::DelLiveFile(err) => fl!("failed-del-live-file", err = err),
             Self::VMKill(err) => fl!("failed-vm-kill", err = err),
+            Self::MonitorCommand(err) => fl!("monitor-command-failed", err = err),
+            Self::VMStillRunning(pid) => fl!("vm-still-running", pid = pid),
         };
         f.write_str(&text)
     }
@@ -66,6 +93,20 @@ pub enum Error {
     NonexistentImage(String),
     MonitorCommand(String),
     FailedLiveVMSe(String),
+    UnsupportedQmp(&'static str),
+    LogFile(String, String),
+    Supervise(String),
+    UnitMissingDisk(String),
+    PreStartHook(String, String),
+    InvalidNetworkName(String),
+    RuntimeDir(String, String),
+    StaleSocket(String, String),
+    MissingBridge(String),
+    MissingTap(String),
+    NetworkSetup(String, String),
+    PortForwardRange(String, String),
+    PortForwardConflict(String, u16),
+    NatAddress(String, String),
 }
 
 impl std::error::Error for Error {}
@@ -105,6 +146,23 @@ impl fmt::Display for Error {
             Self::NonexistentImage(requested_image) => fl!("nonexistent-image", img = requested_image),
             Self::MonitorCommand(err) => fl!("monitor-command-failed", err = err),
             Self::FailedLiveVMSe(err) => fl!("failed-live-vm-se", err = err),
+            Self::UnsupportedQmp(monitor) => {
+                let monitor = *monitor;
+                fl!("unsupported-qmp", monitor = monitor)
+            }
+            Self::LogFile(path, err) => fl!("log-file-error", path = path, err = err),
+            Self::Supervise(err) => fl!("supervise-error", err = err),
+            Self::UnitMissingDisk(disk) => fl!("unit-missing-disk", disk = disk),
+            Self::PreStartHook(command, err) => fl!("pre-start-hook-failed", command = command, err = err),
+            Self::InvalidNetworkName(name) => fl!("invalid-network-name", name = name),
+            Self::RuntimeDir(dir, err) => fl!("runtime-dir-error", dir = dir, err = err),
+            Self::StaleSocket(path, err) => fl!("stale-socket-error", path = path, err = err),
+            Self::MissingBridge(bridge) => fl!("missing-bridge", bridge = bridge),
+            Self::MissingTap(tap) => fl!("missing-tap", tap = tap),
+            Self::NetworkSetup(command, err) => fl!("network-setup-failed", command = command, err = err),
+            Self::PortForwardRange(host, guest) => fl!("port-forward-range", host = host, guest = guest),
+            Self::PortForwardConflict(protocol, port) => fl!("port-forward-conflict", protocol = protocol, port = port),
+            Self::NatAddress(addr, network) => fl!("nat-address", addr = addr, network = network),
         };
         f.write_str(&text)
     }
@@ -115,6 +173,12 @@ pub enum MonitorError {
     NoMonitor,
     Write(std::io::Error),
     Read(std::io::Error),
+    NoQmp,
+    QmpHandshake(String),
+    QmpDeserialize(String),
+    QmpCommand { class: String, desc: String },
+    QmpTimeout,
+    QmpClosed,
 }
 
 impl std::error::Error for MonitorError {}
@@ -124,6 +188,96 @@ impl fmt::Display for MonitorError {
             Self::NoMonitor => fl!("no-monitor-available"),
             Self::Write(err) => fl!("failed-monitor-write", err = err.to_string()),
             Self::Read(err) => fl!("failed-monitor-read", err = err.to_string()),
+            Self::NoQmp => fl!("no-qmp-available"),
+            Self::QmpHandshake(greeting) => fl!("failed-qmp-handshake", greeting = greeting),
+            Self::QmpDeserialize(err) => fl!("failed-qmp-deserialize", err = err),
+            Self::QmpCommand { class, desc } => fl!("qmp-command-error", class = class, desc = desc),
+            Self::QmpTimeout => fl!("qmp-timeout"),
+            Self::QmpClosed => fl!("qmp-closed"),
+        };
+        f.write_str(&text)
+    }
+}
+
+#[derive(Debug)]
+pub enum GuestAgentError {
+    NoGuestAgent,
+    Connect(std::io::Error),
+    Io(std::io::Error),
+    Deserialize(String),
+    Command { class: String, desc: String },
+    Timeout,
+    Closed,
+}
+
+impl std::error::Error for GuestAgentError {}
+impl fmt::Display for GuestAgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::NoGuestAgent => fl!("no-guest-agent"),
+            Self::Connect(err) => fl!("failed-guest-agent-connect", err = err.to_string()),
+            Self::Io(err) => fl!("guest-agent-io-error", err = err.to_string()),
+            Self::Deserialize(err) => fl!("failed-guest-agent-deserialize", err = err),
+            Self::Command { class, desc } => fl!("guest-agent-command-error", class = class, desc = desc),
+            Self::Timeout => fl!("guest-agent-timeout"),
+            Self::Closed => fl!("guest-agent-closed"),
+        };
+        f.write_str(&text)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConsoleError {
+    NoSerial,
+    Connect(std::io::Error),
+    Terminal(std::io::Error),
+    Io(std::io::Error),
+    LogFile(PathBuf, std::io::Error),
+}
+
+impl std::error::Error for ConsoleError {}
+impl fmt::Display for ConsoleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::NoSerial => fl!("no-serial-available"),
+            Self::Connect(err) => fl!("failed-serial-connect", err = err.to_string()),
+            Self::Terminal(err) => fl!("failed-raw-terminal", err = err.to_string()),
+            Self::Io(err) => fl!("console-io-error", err = err.to_string()),
+            Self::LogFile(path, err) => fl!("log-file-error", path = path.display().to_string(), err = err.to_string()),
+        };
+        f.write_str(&text)
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    LiveVM(LiveVMError),
+    Monitor(String),
+    Command(String),
+    QemuImg(String),
+    Deserialize(String),
+    InvalidName(String),
+    UnsupportedFormat(String),
+    NoDisks,
+}
+impl From<LiveVMError> for SnapshotError {
+    fn from(value: LiveVMError) -> Self {
+        Self::LiveVM(value)
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::LiveVM(err) => err.to_string(),
+            Self::Monitor(err) => fl!("snapshot-monitor-failed", err = err),
+            Self::Command(err) => fl!("failed-launch", bin = "qemu-img", err = err),
+            Self::QemuImg(err) => fl!("snapshot-qemu-img-failed", err = err),
+            Self::Deserialize(err) => fl!("failed-qemu-img-deserialization", err = err),
+            Self::InvalidName(name) => fl!("invalid-snapshot-name", name = name),
+            Self::UnsupportedFormat(disk) => fl!("snapshot-unsupported-format", disk = disk),
+            Self::NoDisks => fl!("snapshot-no-disks"),
         };
         f.write_str(&text)
     }
@@ -136,6 +290,13 @@ pub enum Warning {
     #[cfg(target_os = "linux")]
     AudioBackend,
     InsufficientRamConfiguration(Size, GuestOS),
+    DetachedRestart,
+    UnitDisplay(String),
+    UnitNoMonitor,
+    UnitHooks,
+    DetachedHooks,
+    HookOutput(HookStage, String, String),
+    HookFailed(HookStage, String, String),
 }
 
 impl std::error::Error for Warning {}
@@ -154,6 +315,13 @@ impl fmt::Display for Warning {
                 ram = ram.to_string(),
                 guest = guest.to_string()
             ),
+            Self::DetachedRestart => fl!("detached-restart"),
+            Self::UnitDisplay(display) => fl!("unit-display", display = display),
+            Self::UnitNoMonitor => fl!("unit-no-monitor"),
+            Self::UnitHooks => fl!("unit-hooks"),
+            Self::DetachedHooks => fl!("detached-hooks"),
+            Self::HookOutput(stage, command, output) => fl!("hook-output", stage = stage.to_string(), command = command, output = output),
+            Self::HookFailed(stage, command, err) => fl!("hook-failed", stage = stage.to_string(), command = command, err = err),
         };
         f.write_str(&text)
     }
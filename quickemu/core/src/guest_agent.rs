@@ -0,0 +1,459 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::IpAddr,
+    os::unix::net::UnixStream,
+    path::Path,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{error::GuestAgentError, live_vm::LiveVM};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const EXEC_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// The agent reads and writes files in chunks, which are base64 encoded within its JSON messages
+const FILE_CHUNK_SIZE: usize = 48 * 1024;
+// Written by the agent before its response to `guest-sync-delimited`, and read by its parser as the start of a new message
+const DELIMITER: u8 = 0xFF;
+
+impl LiveVM {
+    pub fn guest_agent(&self) -> Result<GuestAgent, GuestAgentError> {
+        let socketpath = self.guest_agent.as_ref().ok_or(GuestAgentError::NoGuestAgent)?;
+        GuestAgent::connect(socketpath)
+    }
+}
+
+// A client for the QEMU guest agent (qemu-ga) running inside the guest. Unlike QMP, the agent has no greeting or events;
+// QEMU only forwards bytes between the socket and the guest's virtio-serial port
+pub struct GuestAgent {
+    stream: UnixStream,
+    reader: BufReader<UnixStream>,
+}
+
+impl GuestAgent {
+    pub fn connect(socketpath: &Path) -> Result<Self, GuestAgentError> {
+        let stream = UnixStream::connect(socketpath).map_err(GuestAgentError::Connect)?;
+        let reader = BufReader::new(stream.try_clone().map_err(GuestAgentError::Connect)?);
+        let mut agent = Self { stream, reader };
+        agent.set_timeout(Some(DEFAULT_TIMEOUT))?;
+        agent.sync()?;
+        Ok(agent)
+    }
+
+    // Sets how long to wait for a command's response. `None` waits indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), GuestAgentError> {
+        self.stream.set_read_timeout(timeout).map_err(GuestAgentError::Io)
+    }
+
+    // The channel outlives clients, so responses to a previous client's requests, or a partial request it left behind,
+    // may still be queued. Syncing discards them, and is also the first point where an agent which isn't running times out
+    fn sync(&mut self) -> Result<(), GuestAgentError> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.subsec_nanos());
+        let id = i64::from(nanos) ^ i64::from(std::process::id());
+        self.stream.write_all(&[DELIMITER]).map_err(GuestAgentError::Io)?;
+        self.send(&GuestSyncDelimited { id })?;
+        loop {
+            let mut discarded = Vec::new();
+            self.reader.read_until(DELIMITER, &mut discarded).map_err(read_error)?;
+            if discarded.last() != Some(&DELIMITER) {
+                return Err(GuestAgentError::Closed);
+            }
+            match self.receive() {
+                Ok(Value::Number(number)) if number.as_i64() == Some(id) => return Ok(()),
+                // The error caused by the delimiter itself, or a stale response
+                Ok(_) | Err(GuestAgentError::Command { .. }) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn execute<C: AgentCommand>(&mut self, command: &C) -> Result<C::Response, GuestAgentError> {
+        self.send(command)?;
+        let value = self.receive()?;
+        serde_json::from_value(value).map_err(|e| GuestAgentError::Deserialize(e.to_string()))
+    }
+
+    pub fn execute_raw(&mut self, command: &str, arguments: Value) -> Result<Value, GuestAgentError> {
+        self.send_raw(command, arguments)?;
+        self.receive()
+    }
+
+    fn send<C: AgentCommand>(&mut self, command: &C) -> Result<(), GuestAgentError> {
+        let arguments = serde_json::to_value(command).map_err(|e| GuestAgentError::Deserialize(e.to_string()))?;
+        self.send_raw(C::NAME, arguments)
+    }
+
+    fn send_raw(&mut self, command: &str, arguments: Value) -> Result<(), GuestAgentError> {
+        let request = AgentRequest {
+            execute: command,
+            arguments: match arguments {
+                Value::Null => None,
+                Value::Object(ref map) if map.is_empty() => None,
+                arguments => Some(arguments),
+            },
+        };
+        let mut data = serde_json::to_vec(&request).map_err(|e| GuestAgentError::Deserialize(e.to_string()))?;
+        data.push(b'\n');
+        self.stream.write_all(&data).map_err(GuestAgentError::Io)
+    }
+
+    fn receive(&mut self) -> Result<Value, GuestAgentError> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line).map_err(read_error)? == 0 {
+                return Err(GuestAgentError::Closed);
+            }
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+        match serde_json::from_str(&line) {
+            Ok(AgentResponse::Return { ret }) => Ok(ret),
+            Ok(AgentResponse::Error { error }) => Err(GuestAgentError::Command { class: error.class, desc: error.desc }),
+            Err(e) => Err(GuestAgentError::Deserialize(format!("{}: {e}", line.trim()))),
+        }
+    }
+
+    pub fn ping(&mut self) -> Result<(), GuestAgentError> {
+        self.execute(&GuestPing {}).map(|_| ())
+    }
+
+    pub fn os_info(&mut self) -> Result<GuestOsInfo, GuestAgentError> {
+        self.execute(&GuestGetOsinfo {})
+    }
+
+    pub fn network_interfaces(&mut self) -> Result<Vec<GuestNetworkInterface>, GuestAgentError> {
+        self.execute(&GuestNetworkGetInterfaces {})
+    }
+
+    // Run a program in the guest and wait for it to exit, collecting its output
+    pub fn exec(&mut self, path: &str, args: &[String], input: Option<&[u8]>) -> Result<GuestExecOutput, GuestAgentError> {
+        let GuestExecPid { pid } = self.execute(&GuestExec {
+            path,
+            arg: args,
+            input_data: input.map(|input| STANDARD.encode(input)),
+            capture_output: true,
+        })?;
+        loop {
+            let status = self.execute(&GuestExecStatus { pid })?;
+            if status.exited {
+                return Ok(GuestExecOutput {
+                    exit_code: status.exitcode,
+                    signal: status.signal,
+                    stdout: status.out_data.as_deref().map(decode).transpose()?.unwrap_or_default(),
+                    stderr: status.err_data.as_deref().map(decode).transpose()?.unwrap_or_default(),
+                    truncated: status.out_truncated || status.err_truncated,
+                });
+            }
+            thread::sleep(EXEC_POLL_INTERVAL);
+        }
+    }
+
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, GuestAgentError> {
+        let handle = self.execute(&GuestFileOpen { path, mode: "r" })?;
+        let result = (|| {
+            let mut data = Vec::new();
+            loop {
+                let chunk = self.execute(&GuestFileRead { handle, count: FILE_CHUNK_SIZE })?;
+                data.extend(decode(&chunk.buf_b64)?);
+                if chunk.eof || chunk.count == 0 {
+                    return Ok(data);
+                }
+            }
+        })();
+        self.close_file(handle, result)
+    }
+
+    pub fn write_file(&mut self, path: &str, data: &[u8]) -> Result<(), GuestAgentError> {
+        let handle = self.execute(&GuestFileOpen { path, mode: "w" })?;
+        let result = data.chunks(FILE_CHUNK_SIZE).try_for_each(|chunk| {
+            self.execute(&GuestFileWrite {
+                handle,
+                buf_b64: STANDARD.encode(chunk),
+            })
+            .map(|_| ())
+        });
+        self.close_file(handle, result)
+    }
+
+    // Files are closed even if reading or writing failed, as the agent otherwise keeps them open until it's restarted
+    fn close_file<T>(&mut self, handle: i64, result: Result<T, GuestAgentError>) -> Result<T, GuestAgentError> {
+        let closed = self.execute(&GuestFileClose { handle });
+        let value = result?;
+        closed?;
+        Ok(value)
+    }
+
+    // The agent doesn't respond to a successful shutdown request, so only errors in sending it are reported
+    pub fn shutdown(&mut self, mode: GuestShutdownMode) -> Result<(), GuestAgentError> {
+        self.send(&GuestShutdown { mode })
+    }
+}
+
+impl Drop for GuestAgent {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+impl std::fmt::Debug for GuestAgent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GuestAgent").finish_non_exhaustive()
+    }
+}
+
+fn read_error(err: io::Error) -> GuestAgentError {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => GuestAgentError::Timeout,
+        _ => GuestAgentError::Io(err),
+    }
+}
+
+fn decode(data: &str) -> Result<Vec<u8>, GuestAgentError> {
+    STANDARD.decode(data).map_err(|e| GuestAgentError::Deserialize(e.to_string()))
+}
+
+#[derive(Serialize)]
+struct AgentRequest<'a> {
+    execute: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arguments: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AgentResponse {
+    Return {
+        #[serde(rename = "return")]
+        ret: Value,
+    },
+    Error {
+        error: AgentErrorDesc,
+    },
+}
+
+#[derive(Deserialize)]
+struct AgentErrorDesc {
+    class: String,
+    desc: String,
+}
+
+pub trait AgentCommand: Serialize {
+    const NAME: &'static str;
+    type Response: DeserializeOwned;
+}
+
+// Response of commands which return an empty object
+#[derive(Clone, Debug, Deserialize)]
+pub struct Empty {}
+
+#[derive(Serialize)]
+struct GuestSyncDelimited {
+    id: i64,
+}
+impl AgentCommand for GuestSyncDelimited {
+    const NAME: &'static str = "guest-sync-delimited";
+    type Response = i64;
+}
+
+#[derive(Serialize)]
+pub struct GuestPing {}
+impl AgentCommand for GuestPing {
+    const NAME: &'static str = "guest-ping";
+    type Response = Empty;
+}
+
+#[derive(Serialize)]
+pub struct GuestGetOsinfo {}
+impl AgentCommand for GuestGetOsinfo {
+    const NAME: &'static str = "guest-get-osinfo";
+    type Response = GuestOsInfo;
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GuestOsInfo {
+    pub kernel_release: Option<String>,
+    pub kernel_version: Option<String>,
+    pub machine: Option<String>,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub pretty_name: Option<String>,
+    pub version: Option<String>,
+    pub version_id: Option<String>,
+    pub variant: Option<String>,
+    pub variant_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct GuestNetworkGetInterfaces {}
+impl AgentCommand for GuestNetworkGetInterfaces {
+    const NAME: &'static str = "guest-network-get-interfaces";
+    type Response = Vec<GuestNetworkInterface>;
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GuestNetworkInterface {
+    pub name: String,
+    pub hardware_address: Option<String>,
+    #[serde(default)]
+    pub ip_addresses: Vec<GuestIpAddress>,
+}
+
+impl GuestNetworkInterface {
+    // Addresses other than loopback, which are how the guest can be reached
+    pub fn routable_addresses(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        self.ip_addresses
+            .iter()
+            .filter_map(|address| address.ip_address.parse::<IpAddr>().ok())
+            .filter(|address| !address.is_loopback())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GuestIpAddress {
+    pub ip_address: String,
+    pub ip_address_type: GuestIpAddressType,
+    pub prefix: u8,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuestIpAddressType {
+    Ipv4,
+    Ipv6,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GuestExec<'a> {
+    pub path: &'a str,
+    pub arg: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_data: Option<String>,
+    pub capture_output: bool,
+}
+impl AgentCommand for GuestExec<'_> {
+    const NAME: &'static str = "guest-exec";
+    type Response = GuestExecPid;
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GuestExecPid {
+    pub pid: i64,
+}
+
+#[derive(Serialize)]
+pub struct GuestExecStatus {
+    pub pid: i64,
+}
+impl AgentCommand for GuestExecStatus {
+    const NAME: &'static str = "guest-exec-status";
+    type Response = GuestExecStatusInfo;
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GuestExecStatusInfo {
+    pub exited: bool,
+    pub exitcode: Option<i32>,
+    pub signal: Option<i32>,
+    pub out_data: Option<String>,
+    pub err_data: Option<String>,
+    #[serde(default)]
+    pub out_truncated: bool,
+    #[serde(default)]
+    pub err_truncated: bool,
+}
+
+// The result of `GuestAgent::exec`, with output decoded
+#[derive(Clone, Debug, Default)]
+pub struct GuestExecOutput {
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    // The agent limits how much output it keeps from each command
+    pub truncated: bool,
+}
+
+#[derive(Serialize)]
+pub struct GuestFileOpen<'a> {
+    pub path: &'a str,
+    pub mode: &'a str,
+}
+impl AgentCommand for GuestFileOpen<'_> {
+    const NAME: &'static str = "guest-file-open";
+    type Response = i64;
+}
+
+#[derive(Serialize)]
+pub struct GuestFileRead {
+    pub handle: i64,
+    pub count: usize,
+}
+impl AgentCommand for GuestFileRead {
+    const NAME: &'static str = "guest-file-read";
+    type Response = GuestFileReadInfo;
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GuestFileReadInfo {
+    pub count: usize,
+    pub buf_b64: String,
+    pub eof: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GuestFileWrite {
+    pub handle: i64,
+    pub buf_b64: String,
+}
+impl AgentCommand for GuestFileWrite {
+    const NAME: &'static str = "guest-file-write";
+    type Response = GuestFileWriteInfo;
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GuestFileWriteInfo {
+    pub count: usize,
+    pub eof: bool,
+}
+
+#[derive(Serialize)]
+pub struct GuestFileClose {
+    pub handle: i64,
+}
+impl AgentCommand for GuestFileClose {
+    const NAME: &'static str = "guest-file-close";
+    type Response = Empty;
+}
+
+#[derive(Serialize)]
+struct GuestShutdown {
+    mode: GuestShutdownMode,
+}
+impl AgentCommand for GuestShutdown {
+    const NAME: &'static str = "guest-shutdown";
+    type Response = Empty;
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuestShutdownMode {
+    #[default]
+    Powerdown,
+    Halt,
+    Reboot,
+}
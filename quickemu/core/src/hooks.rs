@@ -0,0 +1,160 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    path::PathBuf,
+    process::{Command, ExitStatus, Stdio},
+};
+
+use crate::{
+    config::Config,
+    data::{DisplayType, Hooks, MonitorArg, MonitorInner},
+    error::{Error, Warning},
+};
+
+// Hooks are given details of the VM through these variables. They aren't prefixed with `QUICKEMU_`,
+// so that quickemu commands run from a hook don't read them as configuration overrides
+const ENV_PREFIX: &str = "VM_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookStage {
+    PreStart,
+    PostStart,
+    PostStop,
+}
+
+impl fmt::Display for HookStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::PreStart => "pre-start",
+            Self::PostStart => "post-start",
+            Self::PostStop => "post-stop",
+        })
+    }
+}
+
+// The hooks of a VM, along with the environment they're run in
+#[derive(Debug)]
+pub(crate) struct HookRunner {
+    hooks: Hooks,
+    vm_dir: PathBuf,
+    env: Vec<(String, OsString)>,
+}
+
+impl Config {
+    // Should be called once the configuration is finalized, so that the VM name and socket paths are known
+    pub(crate) fn hook_runner(&self) -> HookRunner {
+        // Hooks run from the VM directory, so paths given to them are absolute
+        let vm_dir = self.vm_dir.clone().expect("VM directory should be set");
+        let vm_dir = std::path::absolute(&vm_dir).unwrap_or(vm_dir);
+        let mut env = vec![var("NAME", &self.vm_name), var("DIR", &vm_dir)];
+        env.extend(self.network.ssh_port().map(|port| var("SSH_PORT", port.to_string())));
+        #[cfg(not(target_os = "macos"))]
+        if let DisplayType::Spice { spice_port, .. } = self.io.display.display_type {
+            env.push(var("SPICE_PORT", spice_port.to_string()));
+        }
+        env.extend(monitor_env(&self.network.monitor, "MONITOR"));
+        env.extend(monitor_env(&self.network.serial, "SERIAL"));
+        if let Some(socketpath) = self.guest_agent_socket() {
+            let socketpath = std::path::absolute(&socketpath).unwrap_or(socketpath);
+            env.push(var("GUEST_AGENT_SOCKET", socketpath));
+        }
+        HookRunner {
+            hooks: self.hooks.clone(),
+            vm_dir,
+            env,
+        }
+    }
+}
+
+impl HookRunner {
+    // Run each of a stage's hooks in order, reporting their output as warnings. A failing pre-start hook aborts the launch,
+    // while failures at other stages are only reported
+    pub(crate) fn run(&self, stage: HookStage, pid: Option<u32>, status: Option<ExitStatus>, restarts: u32) -> Result<Vec<Warning>, Error> {
+        let commands = match stage {
+            HookStage::PreStart => &self.hooks.pre_start,
+            HookStage::PostStart => &self.hooks.post_start,
+            HookStage::PostStop => &self.hooks.post_stop,
+        };
+        let mut env = self.env.clone();
+        env.push(var("HOOK", stage.to_string()));
+        env.push(var("RESTARTS", restarts.to_string()));
+        env.extend(pid.map(|pid| var("PID", pid.to_string())));
+        env.extend(
+            status
+                .and_then(|status| status.code())
+                .map(|code| var("EXIT_CODE", code.to_string())),
+        );
+
+        let mut warnings = Vec::new();
+        for command in commands {
+            log::debug!("Running {stage} hook: {command}");
+            let output = shell(command)
+                .current_dir(&self.vm_dir)
+                .envs(env.iter().map(|(name, value)| (name, value)))
+                .stdin(Stdio::null())
+                .output();
+            let err = match output {
+                Ok(output) => {
+                    let text = [output.stdout, output.stderr]
+                        .iter()
+                        .map(|text| String::from_utf8_lossy(text).trim().to_string())
+                        .filter(|text| !text.is_empty())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    match output.status.success() {
+                        true => {
+                            if !text.is_empty() {
+                                warnings.push(Warning::HookOutput(stage, command.clone(), text));
+                            }
+                            continue;
+                        }
+                        false if text.is_empty() => output.status.to_string(),
+                        false => format!("{}: {text}", output.status),
+                    }
+                }
+                Err(e) => e.to_string(),
+            };
+            match stage {
+                HookStage::PreStart => return Err(Error::PreStartHook(command.clone(), err)),
+                _ => warnings.push(Warning::HookFailed(stage, command.clone(), err)),
+            }
+        }
+        Ok(warnings)
+    }
+
+    pub(crate) fn has_post_stop(&self) -> bool {
+        !self.hooks.post_stop.is_empty()
+    }
+}
+
+fn monitor_env<T: MonitorArg>(monitor: &MonitorInner<T>, name: &str) -> Vec<(String, OsString)> {
+    let mut env = Vec::new();
+    let address = match monitor {
+        MonitorInner::Telnet { address } => Some(address.as_ref()),
+        MonitorInner::Qmp { address: Some(address), .. } => Some(address.as_ref()),
+        _ => None,
+    };
+    if let Some(address) = address {
+        env.push(var(&format!("{name}_ADDRESS"), address.to_string()));
+    }
+    #[cfg(unix)]
+    if let Some(socketpath) = monitor.socketpath() {
+        let socketpath = std::path::absolute(socketpath).unwrap_or_else(|_| socketpath.to_path_buf());
+        env.push(var(&format!("{name}_SOCKET"), socketpath));
+    }
+    env
+}
+
+fn var(name: &str, value: impl AsRef<OsStr>) -> (String, OsString) {
+    (format!("{ENV_PREFIX}{name}"), value.as_ref().to_owned())
+}
+
+fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    let (shell, flag) = ("cmd", "/C");
+    #[cfg(not(windows))]
+    let (shell, flag) = ("sh", "-c");
+    let mut shell = Command::new(shell);
+    shell.arg(flag).arg(command);
+    shell
+}
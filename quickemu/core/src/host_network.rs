@@ -0,0 +1,127 @@
+#[cfg(target_os = "linux")]
+use std::path::Path;
+use std::{fmt, process::Command};
+
+use crate::{
+    config::Config,
+    data::{NetworkInterface, NetworkType},
+    error::Error,
+};
+
+// A command which prepares a host network device for the VM. Each is a run of `ip`, which requires root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetupCommand {
+    pub args: Vec<String>,
+}
+
+impl SetupCommand {
+    fn ip(args: &[&str]) -> Self {
+        Self {
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    pub fn run(&self) -> Result<(), Error> {
+        let output = Command::new("ip")
+            .args(&self.args)
+            .output()
+            .map_err(|e| Error::NetworkSetup(self.to_string(), e.to_string()))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::NetworkSetup(self.to_string(), stderr.trim().to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SetupCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ip {}", self.args.join(" "))
+    }
+}
+
+impl Config {
+    // The commands which create the bridges and TAP devices the VM's interfaces use, skipping any which already exist
+    pub fn host_network_setup(&self) -> Vec<SetupCommand> {
+        let mut commands = Vec::new();
+        for interface in &self.network.interfaces {
+            interface.setup_commands(&mut commands);
+        }
+        commands
+    }
+}
+
+impl NetworkInterface {
+    fn setup_commands(&self, commands: &mut Vec<SetupCommand>) {
+        match &self.network_type {
+            NetworkType::Bridged { bridge } => add_bridge(bridge.as_ref(), commands),
+            NetworkType::Tap { tap, bridge } => {
+                if !interface_exists(tap) {
+                    // Owned by the user launching the VM, so that QEMU can open it without root
+                    let mut add = SetupCommand::ip(&["tuntap", "add", "dev", tap, "mode", "tap"]);
+                    if let Some(user) = tap_owner() {
+                        add.args.extend(["user".to_string(), user]);
+                    }
+                    commands.push(add);
+                    commands.push(SetupCommand::ip(&["link", "set", tap, "up"]));
+                }
+                if let Some(bridge) = bridge {
+                    add_bridge(bridge, commands);
+                    if interface_master(tap).as_ref() != Some(bridge) {
+                        commands.push(SetupCommand::ip(&["link", "set", tap, "master", bridge]));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn add_bridge(bridge: &str, commands: &mut Vec<SetupCommand>) {
+    let add = SetupCommand::ip(&["link", "add", "name", bridge, "type", "bridge"]);
+    if !interface_exists(bridge) && !commands.contains(&add) {
+        commands.push(add);
+        commands.push(SetupCommand::ip(&["link", "set", bridge, "up"]));
+    }
+}
+
+// The user a TAP device should be created for. When run through sudo, this is the user who invoked it
+fn tap_owner() -> Option<String> {
+    std::env::var("SUDO_USER")
+        .or_else(|_| std::env::var("USER"))
+        .ok()
+        .filter(|user| !user.is_empty() && user != "root")
+}
+
+// Whether the setup commands can be run directly, rather than only reported
+#[cfg(unix)]
+pub fn is_privileged() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_privileged() -> bool {
+    false
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn interface_exists(name: &str) -> bool {
+    Path::new("/sys/class/net").join(name).exists()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn interface_exists(name: &str) -> bool {
+    sysinfo::Networks::new_with_refreshed_list().contains_key(name)
+}
+
+// The bridge an interface has been added to
+#[cfg(target_os = "linux")]
+fn interface_master(name: &str) -> Option<String> {
+    let master = std::fs::read_link(Path::new("/sys/class/net").join(name).join("master")).ok()?;
+    Some(master.file_name()?.to_string_lossy().into_owned())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn interface_master(_name: &str) -> Option<String> {
+    None
+}
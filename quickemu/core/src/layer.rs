@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use crate::{config::Config, error::ConfigError, migrate, overrides::Overrides, validate::key_at};
+
+impl Config {
+    // User-wide defaults, which every VM configuration is layered over
+    pub fn defaults_file() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("quickemu").join("defaults.toml"))
+    }
+}
+
+// Returns the merged table if the configuration is layered over defaults, includes or overrides, or had to be migrated.
+// Otherwise, the configuration is left to be parsed directly, so that errors retain their location
+pub(crate) fn load(contents: &str, file: &Path, overrides: &Overrides) -> Result<Option<Table>, ConfigError> {
+    let Ok(mut table) = contents.parse::<Table>() else {
+        return Ok(None);
+    };
+    let upgraded = migrate::upgrade(&mut table)?;
+    let defaults = Config::defaults_file().filter(|defaults| defaults.is_file() && !same_file(defaults, file));
+    if !upgraded && defaults.is_none() && overrides.is_empty() && !table.contains_key("include") {
+        return Ok(None);
+    }
+
+    let mut stack: Vec<PathBuf> = file.canonicalize().into_iter().collect();
+    let mut merged = match defaults {
+        Some(defaults) => read_layer(&defaults, &mut stack)?,
+        None => Table::new(),
+    };
+    merge(&mut merged, with_includes(table, file, &mut stack)?);
+    overrides.apply(&mut merged)?;
+    Ok(Some(merged))
+}
+
+// Errors from deserializing a table have no location. Writing the table out and parsing it again gives the error a location
+// within that text, from which the key that failed can be found, and then located in the original file
+pub(crate) fn error_key(table: &Table) -> Option<String> {
+    let contents = toml::to_string(table).ok()?;
+    let span = toml::from_str::<Config>(&contents).err()?.span()?;
+    let document = toml_edit::ImDocument::parse(contents.as_str()).ok()?;
+    key_at(document.as_item(), &span)
+}
+
+fn read_layer(file: &Path, stack: &mut Vec<PathBuf>) -> Result<Table, ConfigError> {
+    let canonical = file
+        .canonicalize()
+        .map_err(|err| ConfigError::Include(file.to_path_buf(), Box::new(err.into())))?;
+    if stack.contains(&canonical) {
+        return Err(ConfigError::IncludeCycle(file.to_path_buf()));
+    }
+    stack.push(canonical);
+    let layer = std::fs::read_to_string(file)
+        .map_err(ConfigError::from)
+        .and_then(|contents| Ok(contents.parse::<Table>()?))
+        .and_then(|mut table| {
+            migrate::upgrade(&mut table)?;
+            with_includes(table, file, stack)
+        })
+        .map_err(|err| match err {
+            ConfigError::Include(..) | ConfigError::IncludeCycle(_) => err,
+            err => ConfigError::Include(file.to_path_buf(), Box::new(err)),
+        });
+    stack.pop();
+    layer
+}
+
+// Included files are merged in order, and the including file is merged over all of them.
+// Relative paths are resolved from the including file's directory
+fn with_includes(mut table: Table, file: &Path, stack: &mut Vec<PathBuf>) -> Result<Table, ConfigError> {
+    let includes: Vec<PathBuf> = match table.remove("include") {
+        Some(includes) => includes.try_into()?,
+        None => return Ok(table),
+    };
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let mut merged = Table::new();
+    for include in includes {
+        merge(&mut merged, read_layer(&dir.join(include), stack)?);
+    }
+    merge(&mut merged, table);
+    Ok(merged)
+}
+
+// Tables are merged key by key, while any other value (including arrays) replaces the one beneath it
+fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    a.canonicalize().ok().zip(b.canonicalize().ok()).is_some_and(|(a, b)| a == b)
+}
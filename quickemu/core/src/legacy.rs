@@ -0,0 +1,573 @@
+use std::{
+    ffi::OsString,
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use toml::{Table, Value};
+
+use crate::{
+    config::{Config, CONFIG_VERSION},
+    data::{GuestOS, KeyboardLayout},
+    error::ConfigError,
+};
+
+// A configuration converted from the shell-style `.conf` files used by the original quickemu
+#[derive(Debug)]
+pub struct LegacyImport {
+    pub config: Config,
+    // Keys which have no equivalent, or whose values could not be converted
+    pub unsupported: Vec<UnsupportedKey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnsupportedKey {
+    pub key: String,
+    pub value: String,
+}
+
+impl fmt::Display for UnsupportedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+impl Config {
+    pub fn import_legacy(file: &Path) -> Result<LegacyImport, ConfigError> {
+        let contents = std::fs::read_to_string(file)?;
+        let file = std::path::absolute(file)?;
+        let conf_dir = file.parent().expect("Config file should have a parent directory");
+        let default_vm_dir = conf_dir.join(file.file_stem().expect("Filename should exist"));
+
+        let mut importer = Importer {
+            assignments: parse_assignments(&contents),
+            conf_dir,
+            vm_dir: default_vm_dir.clone(),
+            table: Table::new(),
+            unsupported: Vec::new(),
+        };
+        let extra_args = importer.convert();
+        let Importer {
+            assignments,
+            vm_dir,
+            table,
+            mut unsupported,
+            ..
+        } = importer;
+        unsupported.extend(
+            assignments
+                .into_iter()
+                .map(|(key, value)| UnsupportedKey { key, value: value.to_string() }),
+        );
+
+        let mut config: Config = Value::Table(table).try_into()?;
+        config.extra_args = extra_args;
+        if vm_dir != default_vm_dir {
+            config.vm_dir = Some(vm_dir);
+        }
+        Ok(LegacyImport { config, unsupported })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum LegacyValue {
+    Scalar(String),
+    Array(Vec<String>),
+}
+
+impl LegacyValue {
+    // Bash expands an array referenced as a scalar to its first element
+    fn into_scalar(self) -> String {
+        match self {
+            Self::Scalar(value) => value,
+            Self::Array(values) => values.into_iter().next().unwrap_or_default(),
+        }
+    }
+    fn into_array(self) -> Vec<String> {
+        match self {
+            Self::Scalar(value) => value.split_whitespace().map(str::to_string).collect(),
+            Self::Array(values) => values,
+        }
+    }
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::Scalar(value) => value.is_empty(),
+            Self::Array(values) => values.is_empty(),
+        }
+    }
+}
+
+impl fmt::Display for LegacyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scalar(value) => f.write_str(value),
+            Self::Array(values) => {
+                let values = values.iter().map(|value| format!("\"{value}\"")).collect::<Vec<_>>();
+                write!(f, "({})", values.join(" "))
+            }
+        }
+    }
+}
+
+struct Importer<'a> {
+    assignments: Vec<(String, LegacyValue)>,
+    conf_dir: &'a Path,
+    vm_dir: PathBuf,
+    table: Table,
+    unsupported: Vec<UnsupportedKey>,
+}
+
+impl Importer<'_> {
+    // Build the configuration table, leaving keys which weren't recognized in `assignments`.
+    // Extra arguments are returned separately, as they're stored as OS strings
+    fn convert(&mut self) -> Vec<OsString> {
+        self.insert(&["version"], i64::from(CONFIG_VERSION));
+        self.convert_guest();
+        self.convert_value("arch", &["machine", "arch"], |arch| match arch {
+            "x86_64" | "amd64" => Some("x86_64".into()),
+            "aarch64" | "arm64" => Some("aarch64".into()),
+            "riscv64" => Some("riscv64".into()),
+            _ => None,
+        });
+        self.convert_boot();
+        self.convert_value("tpm", &["machine", "tpm"], switch);
+        self.convert_value("ram", &["machine", "ram"], size);
+        self.convert_value("cpu_cores", &["machine", "cpu_threads"], |cores| {
+            cores.parse::<u32>().ok().filter(|cores| *cores > 0).map(Value::from)
+        });
+
+        self.convert_images();
+        self.convert_network();
+        self.convert_display();
+
+        self.convert_value("keyboard", &["io", "keyboard"], |keyboard| match keyboard {
+            "usb" | "virtio" => Some(keyboard.into()),
+            "ps2" => Some("PS2".into()),
+            _ => None,
+        });
+        self.convert_value("keyboard_layout", &["io", "keyboard_layout"], |layout| {
+            checked::<KeyboardLayout>(layout.into())
+        });
+        self.convert_value("mouse", &["io", "mouse"], |mouse| match mouse {
+            "usb" | "tablet" | "virtio" => Some(mouse.into()),
+            "ps2" => Some("PS2".into()),
+            _ => None,
+        });
+        self.convert_value("sound_card", &["io", "soundcard"], |soundcard| match soundcard {
+            "none" => Some("none".into()),
+            "intel-hda" => Some("Intel HDA".into()),
+            "ac97" => Some("AC97".into()),
+            "es1370" => Some("ES1370".into()),
+            "sb16" => Some("SB16".into()),
+            "usb-audio" => Some("USB Audio".into()),
+            _ => None,
+        });
+        self.convert_value("usb_controller", &["io", "usb_controller"], |controller| match controller {
+            "none" | "ehci" | "xhci" => Some(controller.into()),
+            _ => None,
+        });
+
+        self.convert_monitor("monitor", 4440);
+        self.convert_monitor("serial", 6660);
+
+        // A disabled public directory can't be expressed in the configuration file
+        self.convert_value("public_dir", &["io", "public_dir"], |public_dir| {
+            (public_dir != "none").then(|| self.conf_dir.join(public_dir).to_string_lossy().into_owned().into())
+        });
+        self.take("extra_args")
+            .map(LegacyValue::into_array)
+            .unwrap_or_default()
+            .into_iter()
+            .map(OsString::from)
+            .collect()
+    }
+
+    fn convert_guest(&mut self) {
+        let Some(os) = self.take_str("guest_os") else {
+            return;
+        };
+        let mut guest = Table::new();
+        let name = match os.as_str() {
+            "freebsd" | "ghostbsd" => os.clone(),
+            // Other BSDs, such as OpenBSD and NetBSD, have no dedicated guest type
+            bsd if bsd.ends_with("bsd") => "bsd".into(),
+            other => other.replace('-', "_"),
+        };
+        guest.insert("os".into(), name.into());
+        let release = match os.as_str() {
+            "macos" => self.take_str("macos_release"),
+            _ => None,
+        };
+        if let Some(release) = &release {
+            guest.insert("release".into(), release.replace('-', "").into());
+        }
+
+        // A macOS guest without a known release can't be represented, so both keys are reported
+        // and the configuration falls back to the default guest
+        match checked::<GuestOS>(Value::Table(guest)) {
+            Some(guest) => self.insert(&["guest"], guest),
+            None => {
+                let guest = Value::try_from(GuestOS::default()).expect("Guest OS should serialize");
+                self.insert(&["guest"], guest);
+                self.unsupported("guest_os", os);
+                if let Some(release) = release {
+                    self.unsupported("macos_release", release);
+                }
+            }
+        }
+    }
+
+    fn convert_boot(&mut self) {
+        let secure_boot = self.take_str("secureboot");
+        match self.take_str("boot").as_deref() {
+            None | Some("efi") => match secure_boot.as_deref() {
+                None | Some("off") => {}
+                Some("on") => {
+                    self.insert(&["machine", "boot", "type"], "efi");
+                    self.insert(&["machine", "boot", "secure_boot"], true);
+                }
+                Some(other) => self.unsupported("secureboot", other),
+            },
+            Some("legacy") => {
+                self.insert(&["machine", "boot", "type"], "legacy");
+                if let Some(secure_boot) = secure_boot.filter(|secure_boot| secure_boot != "off") {
+                    self.unsupported("secureboot", secure_boot);
+                }
+            }
+            Some(other) => self.unsupported("boot", other),
+        }
+    }
+
+    fn convert_images(&mut self) {
+        // The original quickemu keeps all of a VM's files alongside its disk image
+        if let Some(disk_img) = self.take_str("disk_img") {
+            let path = self.conf_dir.join(&disk_img);
+            if let Some(vm_dir) = path.parent() {
+                self.vm_dir = vm_dir.to_path_buf();
+            }
+            let mut disk = Table::new();
+            disk.insert("path".into(), self.image_path(&disk_img));
+            if let Some(disk_size) = self.take_str("disk_size") {
+                match size(&disk_size) {
+                    Some(disk_size) => {
+                        disk.insert("size".into(), disk_size);
+                    }
+                    None => self.unsupported("disk_size", disk_size),
+                }
+            }
+            let format = if path.extension().is_some_and(|ext| ext == "raw") { "raw" } else { "qcow2" };
+            match self.take_str("preallocation").as_deref() {
+                None | Some("off") => {
+                    if format == "raw" {
+                        disk.insert("format".into(), format.into());
+                    }
+                }
+                Some(preallocation @ ("metadata" | "falloc" | "full")) => {
+                    disk.insert("format".into(), format.into());
+                    disk.insert("preallocation".into(), preallocation.into());
+                }
+                Some(other) => self.unsupported("preallocation", other),
+            }
+            self.push(&["images", "disk"], disk);
+        }
+
+        for (key, kind, always_mount) in [("iso", "iso", false), ("fixed_iso", "iso", true), ("img", "img", false)] {
+            if let Some(image) = self.take_str(key) {
+                let mut table = Table::new();
+                table.insert("path".into(), self.image_path(&image));
+                if always_mount {
+                    table.insert("always_mount".into(), true.into());
+                }
+                self.push(&["images", kind], table);
+            }
+        }
+    }
+
+    fn convert_network(&mut self) {
+        let mut interface = Table::new();
+        match self.take_str("network").as_deref() {
+            None | Some("nat" | "user") => self.convert_nat(&mut interface, false),
+            Some("restrict") => self.convert_nat(&mut interface, true),
+            Some("none") => return self.insert(&["network", "interfaces"], Value::Array(Vec::new())),
+            Some(bridge) => {
+                interface.insert("type".into(), "bridged".into());
+                interface.insert("bridge".into(), bridge.into());
+            }
+        }
+        if let Some(mac_addr) = self.take_str("macaddr") {
+            interface.insert("mac_addr".into(), mac_addr.into());
+        }
+        if !interface.is_empty() {
+            self.push(&["network", "interfaces"], interface);
+        }
+    }
+
+    fn convert_nat(&mut self, interface: &mut Table, restrict: bool) {
+        interface.insert("type".into(), "nat".into());
+        if restrict {
+            interface.insert("restrict".into(), true.into());
+        }
+        if let Some(port_forwards) = self.take("port_forwards") {
+            let mut converted = Vec::new();
+            for port_forward in port_forwards.into_array() {
+                let ports = port_forward
+                    .split_once(':')
+                    .and_then(|(host, guest)| Some((host.parse::<u16>().ok()?, guest.parse::<u16>().ok()?)));
+                match ports {
+                    Some((host, guest)) => {
+                        let mut table = Table::new();
+                        table.insert("host".into(), Value::Integer(host.into()));
+                        table.insert("guest".into(), Value::Integer(guest.into()));
+                        converted.push(Value::Table(table));
+                    }
+                    None => self.unsupported("port_forwards", port_forward),
+                }
+            }
+            if !converted.is_empty() {
+                interface.insert("port_forwards".into(), Value::Array(converted));
+            }
+        }
+        if let Some(ssh_port) = self.take_str("ssh_port") {
+            match ssh_port.parse::<u16>() {
+                Ok(port) => {
+                    interface.insert("ssh_port".into(), Value::Integer(port.into()));
+                }
+                Err(_) => self.unsupported("ssh_port", ssh_port),
+            }
+        }
+    }
+
+    fn convert_display(&mut self) {
+        match self.take_str("display").as_deref() {
+            None => {}
+            Some(display @ ("sdl" | "gtk" | "none")) => self.insert(&["io", "display", "type"], display),
+            #[cfg(target_os = "macos")]
+            Some("cocoa") => self.insert(&["io", "display", "type"], "cocoa"),
+            #[cfg(not(target_os = "macos"))]
+            Some("spice") => {
+                self.insert(&["io", "display", "type"], "spice");
+                self.convert_value("spice_port", &["io", "display", "spice_port"], |port| {
+                    port.parse::<u16>().ok().map(|port| Value::Integer(port.into()))
+                });
+                self.convert_value("viewer", &["io", "display", "viewer"], |viewer| match viewer {
+                    "spicy" | "none" => Some(viewer.into()),
+                    "remote-viewer" => Some("remote".into()),
+                    _ => None,
+                });
+            }
+            #[cfg(not(target_os = "macos"))]
+            Some("spice-app") => self.insert(&["io", "display", "type"], "spice_app"),
+            Some(other) => self.unsupported("display", other),
+        }
+        self.convert_value("gl", &["io", "display", "accelerated"], switch);
+        self.convert_value("braille", &["io", "display", "braille"], switch);
+        match (self.take_str("width"), self.take_str("height")) {
+            (Some(width), Some(height)) => match (width.parse::<u32>(), height.parse::<u32>()) {
+                (Ok(width), Ok(height)) => {
+                    self.insert(&["io", "display", "resolution", "type"], "custom");
+                    self.insert(&["io", "display", "resolution", "width"], width);
+                    self.insert(&["io", "display", "resolution", "height"], height);
+                }
+                _ => self.unsupported("resolution", format!("{width}x{height}")),
+            },
+            (Some(width), None) => self.unsupported("width", width),
+            (None, Some(height)) => self.unsupported("height", height),
+            (None, None) => {}
+        }
+        // A full screen display takes precedence over its resolution, as in the original quickemu
+        match self.take_str("fullscreen").as_deref() {
+            None | Some("off") => {}
+            Some("on") => self.insert(
+                &["io", "display", "resolution"],
+                Table::from_iter([("type".to_string(), Value::from("full_screen"))]),
+            ),
+            Some(other) => self.unsupported("fullscreen", other),
+        }
+    }
+
+    fn convert_monitor(&mut self, name: &str, default_port: u16) {
+        match self.take_str(name).as_deref() {
+            None => {}
+            Some("none") => self.insert(&["network", name, "type"], "none"),
+            Some("telnet") => {
+                self.insert(&["network", name, "type"], "telnet");
+                let host = self.take_str(&format!("{name}_telnet_host"));
+                let port = self.take_str(&format!("{name}_telnet_port"));
+                if host.is_some() || port.is_some() {
+                    let host = match host.as_deref() {
+                        None | Some("localhost") => "127.0.0.1",
+                        Some(host) => host,
+                    };
+                    let address = format!("{host}:{}", port.as_deref().unwrap_or(&default_port.to_string()));
+                    match address.parse::<SocketAddr>() {
+                        Ok(address) => self.insert(&["network", name, "address"], address.to_string()),
+                        Err(_) => self.unsupported(&format!("{name}_telnet_host"), address),
+                    }
+                }
+            }
+            #[cfg(unix)]
+            Some("socket") => {
+                self.insert(&["network", name, "type"], "socket");
+                if let Some(socketpath) = self.take_str(&format!("{name}_socketpath")) {
+                    let socketpath = self.conf_dir.join(socketpath);
+                    self.insert(&["network", name, "socketpath"], socketpath.to_string_lossy().into_owned());
+                }
+            }
+            Some(other) => self.unsupported(name, other),
+        }
+    }
+
+    // Later assignments override earlier ones, as they would when the file is sourced
+    fn take(&mut self, key: &str) -> Option<LegacyValue> {
+        let mut value = None;
+        self.assignments.retain(|(name, assigned)| {
+            let matches = name == key;
+            if matches {
+                value = Some(assigned.clone());
+            }
+            !matches
+        });
+        value
+    }
+
+    fn take_str(&mut self, key: &str) -> Option<String> {
+        self.take(key).map(LegacyValue::into_scalar)
+    }
+
+    fn convert_value(&mut self, key: &str, path: &[&str], convert: impl FnOnce(&str) -> Option<Value>) {
+        if let Some(value) = self.take_str(key) {
+            match convert(&value) {
+                Some(converted) => self.insert(path, converted),
+                None => self.unsupported(key, value),
+            }
+        }
+    }
+
+    fn unsupported(&mut self, key: &str, value: impl fmt::Display) {
+        self.unsupported.push(UnsupportedKey {
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+    }
+
+    fn table(&mut self, path: &[&str]) -> &mut Table {
+        path.iter().fold(&mut self.table, |table, part| {
+            table
+                .entry(*part)
+                .or_insert_with(|| Value::Table(Table::new()))
+                .as_table_mut()
+                .expect("Legacy keys should not conflict with tables")
+        })
+    }
+
+    fn insert(&mut self, path: &[&str], value: impl Into<Value>) {
+        let (key, parents) = path.split_last().expect("Path should not be empty");
+        self.table(parents).insert(key.to_string(), value.into());
+    }
+
+    fn push(&mut self, path: &[&str], value: Table) {
+        let (key, parents) = path.split_last().expect("Path should not be empty");
+        if let Value::Array(array) = self.table(parents).entry(*key).or_insert_with(|| Value::Array(Vec::new())) {
+            array.push(Value::Table(value));
+        }
+    }
+
+    // Paths within the VM directory are stored relative to it, as quickget does
+    fn image_path(&self, path: &str) -> Value {
+        let path = self.conf_dir.join(path);
+        let path = path.strip_prefix(&self.vm_dir).unwrap_or(&path);
+        path.to_string_lossy().into_owned().into()
+    }
+}
+
+fn switch(value: &str) -> Option<Value> {
+    match value {
+        "on" => Some(true.into()),
+        "off" => Some(false.into()),
+        _ => None,
+    }
+}
+
+// Sizes are written with either case in legacy configurations
+fn size(value: &str) -> Option<Value> {
+    let value = value.to_uppercase();
+    let number = value.trim_end_matches('B').strip_suffix(['K', 'M', 'G', 'T'])?;
+    number.parse::<f64>().ok().map(|_| value.into())
+}
+
+fn checked<T: DeserializeOwned>(value: Value) -> Option<Value> {
+    value.clone().try_into::<T>().ok().map(|_| value)
+}
+
+fn parse_assignments(contents: &str) -> Vec<(String, LegacyValue)> {
+    let mut assignments = Vec::new();
+    let mut lines = contents.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if key.is_empty() || !key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+            continue;
+        }
+
+        let value = match value.strip_prefix('(') {
+            Some(value) => {
+                let mut value = value.to_string();
+                while !value.contains(')') {
+                    let Some(line) = lines.next() else { break };
+                    value.push('\n');
+                    value.push_str(line);
+                }
+                let end = value.rfind(')').unwrap_or(value.len());
+                LegacyValue::Array(shell_words(&value[..end]))
+            }
+            None => LegacyValue::Scalar(shell_words(value).into_iter().next().unwrap_or_default()),
+        };
+        // Empty values leave the option unset
+        if !value.is_empty() {
+            assignments.push((key.to_string(), value));
+        }
+    }
+    assignments
+}
+
+// Split a string into words as a POSIX shell would, without performing any expansions
+fn shell_words(input: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => word.get_or_insert_default().extend(chars.by_ref().take_while(|&c| c != '\'')),
+            '"' => {
+                let word = word.get_or_insert_default();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(escaped @ ('$' | '`' | '"' | '\\')) => word.push(escaped),
+                            Some('\n') | None => {}
+                            Some(other) => word.extend(['\\', other]),
+                        },
+                        _ => word.push(c),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') | None => {}
+                Some(escaped) => word.get_or_insert_default().push(escaped),
+            },
+            '#' if word.is_none() => chars.by_ref().take_while(|&c| c != '\n').for_each(drop),
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
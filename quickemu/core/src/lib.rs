@@ -1,12 +1,46 @@
 #[cfg(feature = "quickemu")]
 mod args;
 pub mod config;
+#[cfg(feature = "quickemu")]
+pub mod console;
 pub mod data;
 #[cfg(feature = "quickemu")]
 pub mod error;
+#[cfg(all(feature = "quickemu", unix))]
+pub mod guest_agent;
+#[cfg(feature = "quickemu")]
+pub mod hooks;
+#[cfg(feature = "quickemu")]
+pub mod host_network;
 #[cfg(feature = "quickemu")]
 mod i18n;
 #[cfg(feature = "quickemu")]
+mod layer;
+#[cfg(feature = "quickemu")]
+pub mod legacy;
+#[cfg(feature = "quickemu")]
+pub mod libvirt;
+#[cfg(feature = "quickemu")]
 pub mod live_vm;
 #[cfg(feature = "quickemu")]
+mod migrate;
+#[cfg(feature = "quickemu")]
+pub mod overrides;
+#[cfg(feature = "quickemu")]
+pub mod plan;
+#[cfg(feature = "quickemu")]
+pub mod qmp;
+#[cfg(feature = "quickemu")]
+pub mod registry;
+#[cfg(feature = "quickemu")]
+pub mod report;
+#[cfg(feature = "quickemu")]
+pub mod snapshot;
+#[cfg(feature = "quickemu")]
+pub mod supervisor;
+#[cfg(feature = "quickemu")]
+pub mod systemd;
+#[cfg(feature = "quickemu")]
 mod utils;
+#[cfg(feature = "quickemu")]
+pub mod validate;
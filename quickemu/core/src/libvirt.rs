@@ -0,0 +1,829 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use roxmltree::{Document, Node};
+use serde::Serialize;
+use toml::{Table, Value};
+
+use crate::{
+    args::{private_multicast_addr, DriveBus, GUEST_AGENT_PORT},
+    config::{Config, CONFIG_VERSION},
+    data::{Arch, DisplayType, GuestOS, NetDevice, NetworkType, PrivateBackend, WatchdogAction},
+    error::{ConfigError, Error},
+};
+
+// Guest details libvirt has no equivalent for are kept in the domain's metadata, under this namespace
+const METADATA_NAMESPACE: &str = "https://github.com/quickemu-project/quickemu-rs";
+const PUBLIC_DIR_TAG: &str = "Public-quickemu";
+
+// Devices quickemu adds to every VM on its own, which are skipped when importing
+const IMPLIED_DEVICES: &[&str] = &[
+    "emulator",
+    "controller",
+    "input",
+    "video",
+    "serial",
+    "console",
+    "channel",
+    "memballoon",
+    "rng",
+    "sound",
+    "audio",
+    "redirdev",
+    "hub",
+    "panic",
+];
+// Domain settings which quickemu derives from the guest and host
+const IMPLIED_ELEMENTS: &[&str] = &[
+    "uuid",
+    "title",
+    "description",
+    "currentMemory",
+    "features",
+    "cpu",
+    "clock",
+    "on_poweroff",
+    "on_reboot",
+    "on_crash",
+    "pm",
+    "resource",
+    "seclabel",
+];
+
+// A configuration converted from a libvirt domain
+#[derive(Debug)]
+pub struct LibvirtImport {
+    pub config: Config,
+    // Elements which have no equivalent, or which could only be partially converted
+    pub unsupported: Vec<UnsupportedElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnsupportedElement {
+    pub element: String,
+    pub reason: String,
+}
+
+impl fmt::Display for UnsupportedElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}>: {}", self.element, self.reason)
+    }
+}
+
+impl Config {
+    // Describe the VM as a libvirt domain, with the devices `to_full_qemu_args` would create.
+    // Like a dry run, disks are not created and firmware is not copied
+    pub fn to_libvirt_xml(mut self) -> Result<String, Error> {
+        self.fill_defaults();
+        self.network.assign_ssh_ports();
+        // libvirt resolves relative paths against its own working directory, so every image is referenced absolutely
+        let vm_dir = self.vm_dir.clone().expect("VM directory should be set");
+        let vm_dir = std::path::absolute(&vm_dir).unwrap_or(vm_dir);
+        let guest = self.guest;
+        let arch = self.machine.arch;
+
+        let (cpu, _) = self.machine.cpu_args(guest)?;
+        let (ram, _) = self.machine.ram_args(guest)?;
+        let boot = self.machine.boot_args(&vm_dir, guest, true)?;
+        let (machine_type, smm, no_hpet) = self.machine.machine_type(guest);
+        let (images, _) = self
+            .images
+            .args(guest, &vm_dir, self.machine.status_quo, self.network.monitor.clone(), true)?;
+        let hw_virt = arch.enable_hw_virt().unwrap_or(false);
+
+        let mut xml = XmlWriter::default();
+        xml.open("domain", &[("type", if hw_virt { "kvm" } else { "qemu" })]);
+        xml.text("name", &[], &self.vm_name);
+
+        xml.open("metadata", &[]);
+        // The guest is stored as it's written in the configuration, e.g. `os='macos' release='Sonoma'`
+        let guest_attrs: Vec<(String, String)> = match Value::try_from(guest) {
+            Ok(Value::Table(table)) => table
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, value.as_str()?.to_string())))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut attrs = vec![("xmlns:quickemu", METADATA_NAMESPACE)];
+        attrs.extend(guest_attrs.iter().map(|(key, value)| (key.as_str(), value.as_str())));
+        xml.empty("quickemu:guest", &attrs);
+        xml.close("metadata");
+
+        xml.text("memory", &[("unit", "b")], &ram.bytes().to_string());
+        let (sockets, cores, threads) = cpu.topology();
+        xml.text("vcpu", &[], &(sockets * cores * threads).to_string());
+
+        xml.open("os", &[]);
+        xml.text("type", &[("arch", arch_name(arch)), ("machine", machine_type)], "hvm");
+        if let Some((code, vars, secure_boot)) = boot.efi() {
+            xml.text(
+                "loader",
+                &[("readonly", "yes"), ("secure", yes_no(secure_boot)), ("type", "pflash")],
+                &code.to_string_lossy(),
+            );
+            xml.text("nvram", &[], &vars.to_string_lossy());
+        }
+        if let Some(kernel) = boot.kernel() {
+            xml.text("kernel", &[], &kernel.to_string_lossy());
+        }
+        xml.close("os");
+
+        if let Arch::X86_64 { .. } = arch {
+            xml.open("features", &[]);
+            xml.empty("acpi", &[]);
+            xml.empty("apic", &[]);
+            if smm {
+                xml.empty("smm", &[("state", "on")]);
+            }
+            xml.close("features");
+        }
+        let cpu_mode: &[(&str, &str)] = if hw_virt { &[("mode", "host-passthrough")] } else { &[] };
+        xml.open("cpu", cpu_mode);
+        xml.empty(
+            "topology",
+            &[("sockets", &sockets.to_string()), ("dies", "1"), ("cores", &cores.to_string()), ("threads", &threads.to_string())],
+        );
+        xml.close("cpu");
+        if no_hpet {
+            xml.open("clock", &[("offset", "utc")]);
+            xml.empty("timer", &[("name", "hpet"), ("present", "no")]);
+            xml.close("clock");
+        }
+
+        xml.open("devices", &[]);
+        if let Ok(emulator) = which::which(arch.qemu_binary()) {
+            xml.text("emulator", &[], &emulator.to_string_lossy());
+        }
+
+        let mut targets = HashMap::new();
+        for drive in images.drives() {
+            let bus = match drive.bus {
+                DriveBus::Virtio => "virtio",
+                DriveBus::Sata => "sata",
+                DriveBus::Ide => "ide",
+                DriveBus::Default => match machine_type {
+                    "pc" => "ide",
+                    "q35" => "sata",
+                    _ => "scsi",
+                },
+            };
+            let prefix = match bus {
+                "virtio" => "vd",
+                "ide" => "hd",
+                _ => "sd",
+            };
+            let index = targets.entry(prefix).or_insert(0);
+            let dev = format!("{prefix}{}", drive_letters(*index));
+            *index += 1;
+
+            xml.open(
+                "disk",
+                &[("type", "file"), ("device", if drive.cdrom { "cdrom" } else { "disk" })],
+            );
+            xml.empty("driver", &[("name", "qemu"), ("type", drive.format)]);
+            xml.empty("source", &[("file", &drive.path.to_string_lossy())]);
+            xml.empty("target", &[("dev", &dev), ("bus", bus)]);
+            if drive.cdrom {
+                xml.empty("readonly", &[]);
+            }
+            if drive.transient {
+                xml.empty("transient", &[]);
+            }
+            xml.close("disk");
+        }
+
+        for interface in &self.network.interfaces {
+            match &interface.network_type {
+                NetworkType::None => continue,
+                NetworkType::Bridged { bridge } => {
+                    xml.open("interface", &[("type", "bridge")]);
+                    xml.empty("source", &[("bridge", bridge.as_ref())]);
+                }
+                NetworkType::Tap { tap, .. } => {
+                    xml.open("interface", &[("type", "ethernet")]);
+                    xml.empty("target", &[("dev", tap), ("managed", "no")]);
+                }
+                NetworkType::Nat {
+                    port_forwards, ssh_port, settings, ..
+                } => {
+                    xml.open("interface", &[("type", "user")]);
+                    // libvirt only sets the networks, leaving other addresses at QEMU's defaults within them
+                    if let Some(cidr) = settings.cidr {
+                        xml.empty(
+                            "ip",
+                            &[("family", "ipv4"), ("address", &cidr.addr.to_string()), ("prefix", &cidr.prefix.to_string())],
+                        );
+                    }
+                    if let Some(prefix) = settings.ipv6_prefix {
+                        xml.empty(
+                            "ip",
+                            &[("family", "ipv6"), ("address", &prefix.addr.to_string()), ("prefix", &prefix.prefix.to_string())],
+                        );
+                    }
+                    // Port forwarding is only available through the passt backend
+                    if ssh_port.as_ref().is_some() || !port_forwards.is_empty() {
+                        xml.empty("backend", &[("type", "passt")]);
+                    }
+                    if let Some(port) = ssh_port.as_ref() {
+                        xml.open("portForward", &[("proto", "tcp")]);
+                        xml.empty("range", &[("start", &port.to_string()), ("to", "22")]);
+                        xml.close("portForward");
+                    }
+                    // passt forwards to the guest's own address, so guest addresses are dropped
+                    for forward in port_forwards {
+                        let protocol = forward.protocol.to_string();
+                        let host_addr = forward.host_addr.map(|addr| addr.to_string());
+                        let mut attrs = vec![("proto", protocol.as_str())];
+                        attrs.extend(host_addr.as_deref().map(|addr| ("address", addr)));
+                        xml.open("portForward", &attrs);
+                        if forward.host.count() == forward.guest.count() {
+                            let (start, end, to) = (
+                                forward.host.start.to_string(),
+                                forward.host.end.to_string(),
+                                forward.guest.start.to_string(),
+                            );
+                            match forward.host.count() {
+                                1 => xml.empty("range", &[("start", &start), ("to", &to)]),
+                                _ => xml.empty("range", &[("start", &start), ("end", &end), ("to", &to)]),
+                            }
+                        } else {
+                            for (host, guest) in forward.ports() {
+                                xml.empty("range", &[("start", &host.to_string()), ("to", &guest.to_string())]);
+                            }
+                        }
+                        xml.close("portForward");
+                    }
+                }
+                NetworkType::Private {
+                    name,
+                    backend: PrivateBackend::Multicast,
+                    address,
+                } => {
+                    let address = address.unwrap_or_else(|| private_multicast_addr(name));
+                    xml.open("interface", &[("type", "mcast")]);
+                    xml.empty(
+                        "source",
+                        &[("address", &address.ip().to_string()), ("port", &address.port().to_string())],
+                    );
+                }
+                // libvirt can only connect VMs directly over TCP
+                NetworkType::Private { backend: PrivateBackend::Unix, .. } => continue,
+            }
+            if let Some(mac_addr) = &interface.mac_addr {
+                xml.empty("mac", &[("address", mac_addr)]);
+            }
+            xml.empty("model", &[("type", libvirt_nic_model(interface.net_device(guest)))]);
+            xml.close("interface");
+        }
+
+        match &self.io.display.display_type {
+            DisplayType::None => {}
+            #[cfg(not(target_os = "macos"))]
+            DisplayType::Spice { access, spice_port, .. } => {
+                let listen = access.as_ref().map_or("0.0.0.0".to_string(), |address| address.to_string());
+                xml.open(
+                    "graphics",
+                    &[("type", "spice"), ("port", &spice_port.to_string()), ("autoport", "no")],
+                );
+                xml.empty("listen", &[("type", "address"), ("address", &listen)]);
+                xml.close("graphics");
+            }
+            _ => xml.empty("graphics", &[("type", "sdl")]),
+        }
+        if !matches!(self.io.display.display_type, DisplayType::None) {
+            xml.open("video", &[]);
+            xml.empty("model", &[("type", "virtio")]);
+            xml.close("video");
+        }
+
+        if self.machine.tpm {
+            xml.open("tpm", &[("model", "tpm-tis")]);
+            xml.empty("backend", &[("type", "emulator"), ("version", "2.0")]);
+            xml.close("tpm");
+        }
+        if let Some(public_dir) = self
+            .io
+            .public_dir()
+            .filter(|_| !matches!(guest, GuestOS::Windows | GuestOS::WindowsServer))
+        {
+            xml.open("filesystem", &[("type", "mount"), ("accessmode", "mapped")]);
+            xml.empty("source", &[("dir", &public_dir.to_string_lossy())]);
+            xml.empty("target", &[("dir", PUBLIC_DIR_TAG)]);
+            xml.close("filesystem");
+        }
+        // libvirt has no equivalent to QEMU's debug action
+        if let Some(action) = self.machine.watchdog.filter(|action| *action != WatchdogAction::Debug) {
+            xml.empty("watchdog", &[("model", "i6300esb"), ("action", &action.to_string())]);
+        }
+        // libvirt picks the socket path for the agent's channel itself
+        if self.guest_agent {
+            xml.open("channel", &[("type", "unix")]);
+            xml.empty("target", &[("type", "virtio"), ("name", GUEST_AGENT_PORT)]);
+            xml.close("channel");
+        }
+        xml.close("devices");
+        xml.close("domain");
+
+        Ok(xml.finish())
+    }
+
+    // Convert the parts of a libvirt domain which quickemu can represent, reporting anything else
+    pub fn import_libvirt(file: &Path) -> Result<LibvirtImport, ConfigError> {
+        let contents = std::fs::read_to_string(file)?;
+        let document = Document::parse(&contents).map_err(|e| ConfigError::Libvirt(e.to_string()))?;
+        let domain = document.root_element();
+        if !domain.has_tag_name("domain") {
+            return Err(ConfigError::Libvirt(format!(
+                "expected a <domain> element, found <{}>",
+                domain.tag_name().name()
+            )));
+        }
+
+        let mut importer = Importer::default();
+        importer.convert(domain);
+        let Importer { table, unsupported } = importer;
+        let config: Config = Value::Table(table).try_into()?;
+        Ok(LibvirtImport { config, unsupported })
+    }
+}
+
+#[derive(Default)]
+struct Importer {
+    table: Table,
+    unsupported: Vec<UnsupportedElement>,
+}
+
+impl Importer {
+    fn convert(&mut self, domain: Node) {
+        let mut guest = None;
+        let mut interfaces = 0;
+        for element in domain.children().filter(Node::is_element) {
+            match element.tag_name().name() {
+                "name" => self.insert(&["vm_name"], text(element)),
+                "metadata" => {
+                    guest = element
+                        .children()
+                        .find(|child| child.has_tag_name((METADATA_NAMESPACE, "guest")))
+                        .map(|metadata| {
+                            metadata
+                                .attributes()
+                                .map(|attr| (attr.name().to_string(), Value::String(attr.value().to_string())))
+                                .collect::<Table>()
+                        })
+                        .or(guest);
+                }
+                "memory" => match memory_bytes(element) {
+                    Some(bytes) => self.insert(&["machine", "ram"], bytes),
+                    None => self.unsupported(element, "memory size could not be read"),
+                },
+                "vcpu" => match text(element).parse::<i64>() {
+                    Ok(threads) if threads > 0 => self.insert(&["machine", "cpu_threads"], threads),
+                    _ => self.unsupported(element, "CPU count could not be read"),
+                },
+                "os" => self.convert_os(element),
+                "devices" => {
+                    for device in element.children().filter(Node::is_element) {
+                        match device.tag_name().name() {
+                            "disk" => self.convert_disk(device),
+                            "interface" => {
+                                interfaces += 1;
+                                self.convert_interface(device);
+                            }
+                            "graphics" => self.convert_graphics(device),
+                            "tpm" => match device
+                                .children()
+                                .find(|child| child.has_tag_name("backend"))
+                                .and_then(|backend| backend.attribute("type"))
+                            {
+                                Some("emulator") => self.insert(&["machine", "tpm"], true),
+                                _ => self.unsupported(device, "only emulated TPMs are supported"),
+                            },
+                            "filesystem" => match child_attr(device, "source", "dir") {
+                                Some(dir) if device.attribute("type").is_none_or(|kind| kind == "mount") => self.insert(&["io", "public_dir"], dir),
+                                _ => self.unsupported(device, "only directories can be shared"),
+                            },
+                            "watchdog" => match device.attribute("action").unwrap_or("reset") {
+                                "dump" => self.unsupported(device, "the dump action is not supported"),
+                                action => self.insert(&["machine", "watchdog"], action),
+                            },
+                            "channel" if child_attr(device, "target", "name") == Some(GUEST_AGENT_PORT) => self.insert(&["guest_agent"], true),
+                            name if IMPLIED_DEVICES.contains(&name) => {}
+                            _ => self.unsupported(device, "not supported"),
+                        }
+                    }
+                }
+                name if IMPLIED_ELEMENTS.contains(&name) => {}
+                _ => self.unsupported(element, "not supported"),
+            }
+        }
+        if interfaces == 0 {
+            self.insert(&["network", "interfaces"], Value::Array(Vec::new()));
+        }
+        // Displays which couldn't be converted are left as quickemu's default
+        if !domain.descendants().any(|node| node.has_tag_name("graphics")) {
+            self.insert(&["io", "display", "type"], "none");
+        }
+
+        let guest = guest.unwrap_or_else(|| {
+            let os = domain
+                .descendants()
+                .find(|node| node.tag_name().name() == "os" && node.attribute("id").is_some())
+                .and_then(|node| node.attribute("id"))
+                .map_or("linux", libosinfo_guest);
+            Table::from_iter([("os".to_string(), Value::String(os.to_string()))])
+        });
+        self.table.insert("guest".to_string(), Value::Table(guest));
+        self.insert(&["version"], i64::from(CONFIG_VERSION));
+    }
+
+    fn convert_os(&mut self, os: Node) {
+        if let Some(arch) = os
+            .children()
+            .find(|child| child.has_tag_name("type"))
+            .and_then(|kind| kind.attribute("arch"))
+        {
+            match arch {
+                "x86_64" | "aarch64" | "riscv64" => self.insert(&["machine", "arch"], arch),
+                _ => self.unsupported(os, &format!("architecture {arch} is not supported")),
+            }
+        }
+        let loader = os.children().find(|child| child.has_tag_name("loader"));
+        let efi = os.attribute("firmware") == Some("efi") || loader.is_some_and(|loader| loader.attribute("type") == Some("pflash"));
+        let secure_boot = loader.is_some_and(|loader| loader.attribute("secure") == Some("yes"))
+            || os
+                .descendants()
+                .any(|feature| feature.has_tag_name("feature") && feature.attribute("name") == Some("secure-boot") && feature.attribute("enabled") == Some("yes"));
+        match efi {
+            true => {
+                self.insert(&["machine", "boot", "type"], "efi");
+                self.insert(&["machine", "boot", "secure_boot"], secure_boot);
+            }
+            // BIOS is only implied on x86_64, which is also libvirt's default
+            false
+                if os
+                    .children()
+                    .find(|child| child.has_tag_name("type"))
+                    .and_then(|kind| kind.attribute("arch"))
+                    .is_none_or(|arch| arch == "x86_64") =>
+            {
+                self.insert(&["machine", "boot", "type"], "legacy")
+            }
+            false => {}
+        }
+        if os.children().any(|child| child.has_tag_name("kernel")) {
+            self.unsupported(os, "direct kernel boot is not supported");
+        }
+    }
+
+    fn convert_disk(&mut self, disk: Node) {
+        let device = disk.attribute("device").unwrap_or("disk");
+        let source = child_attr(disk, "source", "file");
+        if disk.attribute("type").is_some_and(|kind| kind != "file") {
+            return self.unsupported(disk, "only file-backed disks are supported");
+        }
+        match (device, source) {
+            ("disk", Some(path)) => {
+                let mut image = Table::new();
+                image.insert("path".to_string(), path.into());
+                match child_attr(disk, "driver", "type") {
+                    Some(format @ ("qcow2" | "raw" | "qed" | "qcow" | "vdi" | "vpc" | "vhdx")) => {
+                        image.insert("format".to_string(), format.into());
+                    }
+                    Some(format) => self.unsupported(disk, &format!("disk format {format} is not supported")),
+                    None => {}
+                }
+                if disk.children().any(|child| child.has_tag_name("transient")) {
+                    self.insert(&["machine", "status_quo"], true);
+                }
+                self.push(&["images", "disk"], image);
+            }
+            ("cdrom", Some(path)) => {
+                let image = Table::from_iter([("path".to_string(), Value::from(path))]);
+                self.push(&["images", "iso"], image);
+            }
+            // Empty CD-ROM drives are added by quickemu where they're needed
+            ("cdrom", None) => {}
+            _ => self.unsupported(disk, &format!("{device} devices are not supported")),
+        }
+    }
+
+    fn convert_interface(&mut self, interface: Node) {
+        let mut converted = Table::new();
+        match interface.attribute("type") {
+            Some(kind @ ("user" | "network")) => {
+                converted.insert("type".into(), "nat".into());
+                if kind == "network" {
+                    self.unsupported(
+                        interface,
+                        "libvirt networks are not supported, user networking will be used instead",
+                    );
+                }
+                for ip in interface.children().filter(|child| child.has_tag_name("ip")) {
+                    let key = match ip.attribute("family") {
+                        Some("ipv6") => "ipv6_prefix",
+                        _ => "cidr",
+                    };
+                    match (ip.attribute("address"), ip.attribute("prefix")) {
+                        (Some(address), Some(prefix)) => {
+                            converted.insert(key.into(), format!("{address}/{prefix}").into());
+                        }
+                        _ => self.unsupported(ip, "only networks with a prefix are supported"),
+                    }
+                }
+                let mut port_forwards = Vec::new();
+                for forward in interface.children().filter(|child| child.has_tag_name("portForward")) {
+                    let protocol = forward.attribute("proto").unwrap_or("tcp");
+                    if !matches!(protocol, "tcp" | "udp") {
+                        self.unsupported(forward, &format!("{protocol} ports cannot be forwarded"));
+                        continue;
+                    }
+                    let host_addr = match forward.attribute("address").map(|addr| addr.parse::<std::net::Ipv4Addr>()) {
+                        Some(Ok(addr)) => Some(addr),
+                        Some(Err(_)) => {
+                            self.unsupported(forward, "only IPv4 host addresses are supported");
+                            continue;
+                        }
+                        None => None,
+                    };
+                    for range in forward.children().filter(|child| child.has_tag_name("range")) {
+                        let start = range.attribute("start").and_then(|port| port.parse::<u16>().ok());
+                        let end = match range.attribute("end") {
+                            Some(end) => end.parse::<u16>().ok(),
+                            None => start,
+                        };
+                        let to = match range.attribute("to") {
+                            Some(to) => to.parse::<u16>().ok(),
+                            None => start,
+                        };
+                        let (Some(start), Some(end), Some(to)) = (start, end, to) else {
+                            self.unsupported(range, "the ports could not be read");
+                            continue;
+                        };
+                        let Some(to_end) = to.checked_add(end.saturating_sub(start)).filter(|_| start <= end) else {
+                            self.unsupported(range, "the port range is invalid");
+                            continue;
+                        };
+                        if protocol == "tcp" && host_addr.is_none() && start == end && to == 22 {
+                            converted.insert("ssh_port".into(), i64::from(start).into());
+                            continue;
+                        }
+                        let mut forward = Table::new();
+                        if protocol != "tcp" {
+                            forward.insert("protocol".into(), protocol.into());
+                        }
+                        if let Some(host_addr) = host_addr {
+                            forward.insert("host_addr".into(), host_addr.to_string().into());
+                        }
+                        forward.insert("host".into(), port_range_value(start, end));
+                        forward.insert("guest".into(), port_range_value(to, to_end));
+                        port_forwards.push(Value::Table(forward));
+                    }
+                }
+                if !port_forwards.is_empty() {
+                    converted.insert("port_forwards".into(), Value::Array(port_forwards));
+                }
+            }
+            // Only TAP devices created outside of libvirt can be used
+            Some("ethernet") => match (
+                child_attr(interface, "target", "dev"),
+                child_attr(interface, "target", "managed"),
+            ) {
+                (Some(tap), Some("no")) => {
+                    converted.insert("type".into(), "tap".into());
+                    converted.insert("tap".into(), tap.into());
+                }
+                _ => return self.unsupported(interface, "only unmanaged TAP devices are supported"),
+            },
+            Some("mcast") => {
+                let address = child_attr(interface, "source", "address").and_then(|address| address.parse::<std::net::Ipv4Addr>().ok());
+                let port = child_attr(interface, "source", "port").and_then(|port| port.parse::<u16>().ok());
+                let (Some(address), Some(port)) = (address, port) else {
+                    return self.unsupported(interface, "the multicast group could not be read");
+                };
+                converted.insert("type".into(), "private".into());
+                converted.insert("name".into(), format!("{address}-{port}").into());
+                converted.insert("address".into(), format!("{address}:{port}").into());
+            }
+            Some("bridge") => match child_attr(interface, "source", "bridge") {
+                Some(bridge) => {
+                    converted.insert("type".into(), "bridged".into());
+                    converted.insert("bridge".into(), bridge.into());
+                }
+                None => return self.unsupported(interface, "the bridge could not be read"),
+            },
+            kind => {
+                return self.unsupported(
+                    interface,
+                    &format!("{} interfaces are not supported", kind.unwrap_or("untyped")),
+                )
+            }
+        }
+        if let Some(mac_addr) = child_attr(interface, "mac", "address") {
+            converted.insert("mac_addr".into(), mac_addr.into());
+        }
+        match child_attr(interface, "model", "type").map(net_device) {
+            Some(Some(model)) => {
+                converted.insert("model".into(), model.into());
+            }
+            Some(None) => self.unsupported(interface, "the NIC model is not supported, the guest's default will be used"),
+            None => {}
+        }
+        self.push(&["network", "interfaces"], converted);
+    }
+
+    fn convert_graphics(&mut self, graphics: Node) {
+        if self.table.get("io").and_then(|io| io.get("display")).is_some() {
+            return self.unsupported(graphics, "only one display is supported");
+        }
+        match graphics.attribute("type") {
+            Some("spice") => {
+                self.insert(&["io", "display", "type"], "spice");
+                if let Some(port) = graphics.attribute("port").and_then(|port| port.parse::<u16>().ok()) {
+                    self.insert(&["io", "display", "spice_port"], i64::from(port));
+                }
+                let listen = graphics
+                    .attribute("listen")
+                    .or_else(|| child_attr(graphics, "listen", "address"));
+                if let Some(listen) = listen {
+                    self.insert(&["io", "display", "access"], listen);
+                }
+            }
+            Some(kind @ ("sdl" | "gtk")) => self.insert(&["io", "display", "type"], kind),
+            kind => self.unsupported(graphics, &format!("{} graphics are not supported", kind.unwrap_or("untyped"))),
+        }
+    }
+
+    fn unsupported(&mut self, element: Node, reason: &str) {
+        self.unsupported.push(UnsupportedElement {
+            element: element.tag_name().name().to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    fn table(&mut self, path: &[&str]) -> &mut Table {
+        path.iter().fold(&mut self.table, |table, part| {
+            table
+                .entry(*part)
+                .or_insert_with(|| Value::Table(Table::new()))
+                .as_table_mut()
+                .expect("Imported keys should not conflict with tables")
+        })
+    }
+
+    fn insert(&mut self, path: &[&str], value: impl Into<Value>) {
+        let (key, parents) = path.split_last().expect("Path should not be empty");
+        self.table(parents).insert(key.to_string(), value.into());
+    }
+
+    fn push(&mut self, path: &[&str], value: Table) {
+        let (key, parents) = path.split_last().expect("Path should not be empty");
+        if let Value::Array(array) = self.table(parents).entry(*key).or_insert_with(|| Value::Array(Vec::new())) {
+            array.push(Value::Table(value));
+        }
+    }
+}
+
+fn text<'a>(node: Node<'a, '_>) -> &'a str {
+    node.text().map_or("", str::trim)
+}
+
+fn child_attr<'a>(node: Node<'a, '_>, child: &str, attr: &str) -> Option<&'a str> {
+    node.children()
+        .find(|node| node.has_tag_name(child))
+        .and_then(|node| node.attribute(attr))
+}
+
+// libvirt sizes default to KiB
+fn memory_bytes(memory: Node) -> Option<i64> {
+    let value = text(memory).parse::<i64>().ok()?;
+    let multiplier: i64 = match memory.attribute("unit").unwrap_or("KiB") {
+        "b" | "bytes" => 1,
+        "KB" => 1000,
+        "k" | "KiB" => 1 << 10,
+        "MB" => 1000_i64.pow(2),
+        "M" | "MiB" => 1 << 20,
+        "GB" => 1000_i64.pow(3),
+        "G" | "GiB" => 1 << 30,
+        "TB" => 1000_i64.pow(4),
+        "T" | "TiB" => 1 << 40,
+        _ => return None,
+    };
+    value.checked_mul(multiplier)
+}
+
+// Guess the guest from a libosinfo ID, e.g. `http://microsoft.com/win/11`
+fn libosinfo_guest(id: &str) -> &'static str {
+    if id.contains("microsoft.com/win/") {
+        match id.contains("/win/20") {
+            true => "windows_server",
+            false => "windows",
+        }
+    } else if id.contains("freebsd.org") {
+        "freebsd"
+    } else if id.contains("haiku-os.org") {
+        "haiku"
+    } else if id.contains("reactos.org") {
+        "reactos"
+    } else {
+        "linux"
+    }
+}
+
+fn arch_name(arch: Arch) -> &'static str {
+    match arch {
+        Arch::X86_64 { .. } => "x86_64",
+        Arch::AArch64 { .. } => "aarch64",
+        Arch::Riscv64 { .. } => "riscv64",
+    }
+}
+
+// Single ports are written as numbers, as most configurations do
+fn port_range_value(start: u16, end: u16) -> Value {
+    match start == end {
+        true => Value::from(i64::from(start)),
+        false => Value::from(format!("{start}-{end}")),
+    }
+}
+
+fn libvirt_nic_model(device: NetDevice) -> &'static str {
+    match device {
+        NetDevice::VirtioNet => "virtio",
+        device => device.model(),
+    }
+}
+
+// The name of the `NetDevice` matching a libvirt NIC model
+fn net_device(model: &str) -> Option<&'static str> {
+    match model {
+        "virtio" | "virtio-net" => Some("virtio_net"),
+        "e1000" => Some("e1000"),
+        "vmxnet3" => Some("vmxnet3"),
+        "rtl8139" => Some("rtl8139"),
+        _ => None,
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    match value {
+        true => "yes",
+        false => "no",
+    }
+}
+
+// Target device names continue from `z` to `aa`, as libvirt expects
+fn drive_letters(mut index: usize) -> String {
+    let mut letters = Vec::new();
+    loop {
+        letters.push(b'a' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    letters.iter().rev().map(|&letter| letter as char).collect()
+}
+
+#[derive(Default)]
+struct XmlWriter {
+    xml: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    fn start_tag(&mut self, tag: &str, attrs: &[(&str, &str)]) {
+        self.xml.push_str(&"  ".repeat(self.depth));
+        self.xml.push('<');
+        self.xml.push_str(tag);
+        for (name, value) in attrs {
+            self.xml.push_str(&format!(" {name}='{}'", escape(value)));
+        }
+    }
+    fn open(&mut self, tag: &str, attrs: &[(&str, &str)]) {
+        self.start_tag(tag, attrs);
+        self.xml.push_str(">\n");
+        self.depth += 1;
+    }
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.xml.push_str(&format!("{}</{tag}>\n", "  ".repeat(self.depth)));
+    }
+    fn empty(&mut self, tag: &str, attrs: &[(&str, &str)]) {
+        self.start_tag(tag, attrs);
+        self.xml.push_str("/>\n");
+    }
+    fn text(&mut self, tag: &str, attrs: &[(&str, &str)], text: &str) {
+        self.start_tag(tag, attrs);
+        self.xml.push_str(&format!(">{}</{tag}>\n", escape(text)));
+    }
+    fn finish(self) -> String {
+        self.xml
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&apos;")
+        .replace('"', "&quot;")
+}
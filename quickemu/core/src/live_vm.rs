@@ -2,25 +2,65 @@ use std::{
     fs::File,
     io::Write,
     path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
 };
 
 use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, Process, ProcessRefreshKind, ProcessStatus, Signal, System};
 
 use crate::{
-    data::{Monitor, Serial},
+    data::{Monitor, MonitorInner, Serial},
     error::{Error, LiveVMError, MonitorError},
+    qmp::{Cont, KeyValue, QmpClient, QmpCommand, SendKey, Stop, SystemPowerdown, SystemReset},
 };
 
 const LIVE_VM_FILENAME: &str = "quickemu-live.toml";
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(5);
+const KILL_TIMEOUT: Duration = Duration::from_secs(1);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+#[cfg(target_os = "linux")]
+const MAX_PROCESS_NAME_LEN: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShutdownMethod {
+    Powerdown,
+    Terminated,
+    Killed,
+}
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct LiveVM {
     pub pid: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<u64>,
+    #[serde(default)]
+    pub process_name: String,
     pub ssh_port: Option<u16>,
     #[cfg(not(target_os = "macos"))]
     pub spice_port: Option<u16>,
     pub monitor: Monitor,
     pub serial: Serial,
+    // The socket connected to the guest agent's virtio-serial port, when it's enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest_agent: Option<PathBuf>,
+    #[serde(default)]
+    pub disks: Vec<PathBuf>,
+    // Where QEMU's output is written, when the VM was launched detached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+    // How many times the supervisor has restarted QEMU under the VM's restart policy
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub restarts: u32,
+    // Set once the VM is stopped through quickemu, so that it isn't restarted
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stopping: bool,
+    #[serde(skip)]
+    file: PathBuf,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
 }
 
 impl LiveVM {
@@ -32,7 +72,8 @@ impl LiveVM {
         }
 
         let data = std::fs::read_to_string(&expected_path).map_err(|e| LiveVMError::LiveVMDe(e.to_string()))?;
-        let live_vm: Self = toml::from_str(&data).map_err(|e| LiveVMError::LiveVMDe(e.to_string()))?;
+        let mut live_vm: Self = toml::from_str(&data).map_err(|e| LiveVMError::LiveVMDe(e.to_string()))?;
+        live_vm.file = expected_path.clone();
 
         if live_vm.is_active() {
             Ok(Some(live_vm))
@@ -44,50 +85,183 @@ impl LiveVM {
     pub fn send_monitor_cmd(&self, cmd: &str) -> Result<String, MonitorError> {
         self.monitor.send_cmd(cmd)
     }
-    fn is_active(&self) -> bool {
-        #[cfg(unix)]
+    pub fn qmp(&self) -> Result<QmpClient, MonitorError> {
+        self.monitor.qmp()
+    }
+    fn monitor_action<C: QmpCommand>(&self, hmp: &str, qmp: C) -> Result<(), LiveVMError> {
+        let result = match self.monitor {
+            MonitorInner::Qmp { .. } => self.qmp().and_then(|mut client| client.execute(&qmp).map(|_| ())),
+            _ => self.monitor.send_cmd(hmp).map(|_| ()),
+        };
+        result.map_err(|e| LiveVMError::MonitorCommand(e.to_string()))
+    }
+    // Requests an ACPI shutdown, falling back to SIGTERM and then SIGKILL if the VM hasn't exited within the timeout
+    pub fn shutdown(&self, timeout: Duration) -> Result<ShutdownMethod, LiveVMError> {
+        self.mark_stopping();
+        match self.monitor_action("system_powerdown", SystemPowerdown {}) {
+            Ok(()) => {
+                log::info!("Sent ACPI shutdown request to VM (PID {})", self.pid);
+                if self.wait_for_exit(timeout) {
+                    return Ok(ShutdownMethod::Powerdown);
+                }
+                log::warn!("VM did not shut down within {}s, terminating it", timeout.as_secs());
+            }
+            Err(e) => log::warn!("Could not request ACPI shutdown, terminating VM: {e}"),
+        }
+
+        match self.signal(Signal::Term) {
+            Ok(()) if self.wait_for_exit(TERMINATE_TIMEOUT) => return Ok(ShutdownMethod::Terminated),
+            Ok(()) => log::warn!("VM did not exit after SIGTERM, killing it"),
+            Err(e) => log::warn!("Could not terminate VM, killing it: {e}"),
+        }
+
+        self.kill()?;
+        if self.wait_for_exit(KILL_TIMEOUT) {
+            Ok(ShutdownMethod::Killed)
+        } else {
+            Err(LiveVMError::VMStillRunning(self.pid))
+        }
+    }
+    // Sends Ctrl+Alt+Delete to the guest, which most operating systems treat as a reboot request
+    pub fn reboot(&self) -> Result<(), LiveVMError> {
+        let keys = ["ctrl", "alt", "delete"].into_iter().map(KeyValue::Qcode).collect();
+        self.monitor_action("sendkey ctrl-alt-delete", SendKey { keys, hold_time: None })
+    }
+    pub fn reset(&self) -> Result<(), LiveVMError> {
+        self.monitor_action("system_reset", SystemReset {})
+    }
+    pub fn pause(&self) -> Result<(), LiveVMError> {
+        self.monitor_action("stop", Stop {})
+    }
+    pub fn resume(&self) -> Result<(), LiveVMError> {
+        self.monitor_action("cont", Cont {})
+    }
+    fn wait_for_exit(&self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while self.is_active() {
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        true
+    }
+    pub fn is_active(&self) -> bool {
+        self.with_process(|_| ()).is_some()
+    }
+    // Runs the closure on the VM's process, as long as the PID still belongs to the QEMU process which was launched
+    fn with_process<T>(&self, f: impl FnOnce(&Process) -> T) -> Option<T> {
+        let pid = Pid::from_u32(self.pid);
+        let mut system = System::new();
+        if !system.refresh_process_specifics(pid, ProcessRefreshKind::new()) {
+            return None;
+        }
+        system
+            .process(pid)
+            .filter(|process| process.status() != ProcessStatus::Zombie)
+            .filter(|process| self.start_time.is_none_or(|start_time| process.start_time() == start_time))
+            .filter(|process| self.is_vm_process(process))
+            .map(f)
+    }
+    fn is_vm_process(&self, process: &Process) -> bool {
+        let name = process.name();
+        // QEMU only renames itself once it has parsed its arguments, and outside of Linux it keeps the binary's name.
+        // As any other VM could have the same name, it's only accepted when the process also started at the recorded time
+        if name.starts_with("qemu-system") {
+            return self.start_time == Some(process.start_time());
+        }
+        #[cfg(target_os = "linux")]
+        {
+            // Linux truncates process names to 15 bytes
+            let mut expected_len = self.process_name.len().min(MAX_PROCESS_NAME_LEN);
+            while !self.process_name.is_char_boundary(expected_len) {
+                expected_len -= 1;
+            }
+            name.len() >= expected_len && name.as_bytes()[..expected_len] == self.process_name.as_bytes()[..expected_len]
+        }
+        #[cfg(not(target_os = "linux"))]
         {
-            std::process::Command::new("kill")
-                .arg("-0")
-                .arg(self.pid.to_string())
-                .stdout(std::process::Stdio::null())
-                .output()
-                .is_ok_and(|output| output.status.success())
+            name == self.process_name
         }
     }
     pub fn kill(&self) -> Result<(), LiveVMError> {
-        #[cfg(unix)]
-        {
-            std::process::Command::new("kill")
-                .arg("-9")
-                .arg(self.pid.to_string())
-                .output()
-                .map_err(|e| LiveVMError::VMKill(e.to_string()))?;
-            Ok(())
+        self.mark_stopping();
+        self.signal(Signal::Kill)
+    }
+    // Record that the VM is being stopped deliberately, for the supervisor to read once QEMU exits
+    fn mark_stopping(&self) {
+        if self.stopping || self.file.as_os_str().is_empty() {
+            return;
+        }
+        let live_vm = Self { stopping: true, ..self.clone() };
+        let result = toml::to_string_pretty(&live_vm)
+            .map_err(|e| e.to_string())
+            .and_then(|data| std::fs::write(&self.file, data).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::debug!("Could not mark VM as stopping: {e}");
+        }
+    }
+    pub(crate) fn stop_requested(&self) -> bool {
+        std::fs::read_to_string(&self.file)
+            .ok()
+            .and_then(|data| toml::from_str::<Self>(&data).ok())
+            .is_some_and(|live_vm| live_vm.stopping)
+    }
+    fn signal(&self, signal: Signal) -> Result<(), LiveVMError> {
+        match self.with_process(|process| process.kill_with(signal)) {
+            // The process has already exited
+            None => Ok(()),
+            Some(Some(true)) => Ok(()),
+            Some(Some(false)) => Err(LiveVMError::VMKill(format!("Could not send {signal} to process {}", self.pid))),
+            Some(None) => Err(LiveVMError::VMKill(format!("{signal} is not supported on this platform"))),
         }
     }
-    pub(crate) fn new(vm_dir: &Path, ssh_port: Option<u16>, #[cfg(not(target_os = "macos"))] spice_port: Option<u16>, monitor: Monitor, serial: Serial) -> (Self, PathBuf) {
+    pub(crate) fn new(
+        vm_dir: &Path, process_name: &str, ssh_port: Option<u16>, #[cfg(not(target_os = "macos"))] spice_port: Option<u16>, monitor: Monitor, serial: Serial, disks: Vec<PathBuf>,
+    ) -> (Self, PathBuf) {
         (
             Self {
                 pid: 0,
+                start_time: None,
+                process_name: process_name.to_string(),
                 ssh_port,
                 #[cfg(not(target_os = "macos"))]
                 spice_port,
                 monitor,
                 serial,
+                guest_agent: None,
+                disks,
+                log_file: None,
+                restarts: 0,
+                stopping: false,
+                file: vm_dir.join(LIVE_VM_FILENAME),
             },
             vm_dir.join(LIVE_VM_FILENAME),
         )
     }
-    pub(crate) fn serialize(mut self, file: &Path, pid: u32) -> Result<(), Error> {
+    // Used for VMs which quickemu didn't launch, and so have no live VM file
+    pub(crate) fn attach(mut self, pid: u32) -> Self {
+        self.set_pid(pid);
+        self.file = PathBuf::new();
+        self
+    }
+    // The process is known to be the VM's, so its start time is read without checking its name
+    fn set_pid(&mut self, pid: u32) {
         self.pid = pid;
+        let pid = Pid::from_u32(pid);
+        let mut system = System::new();
+        system.refresh_process_specifics(pid, ProcessRefreshKind::new());
+        self.start_time = system.process(pid).map(Process::start_time);
+    }
+    pub(crate) fn save(&mut self, file: &Path, pid: u32) -> Result<(), Error> {
+        self.set_pid(pid);
         if file.exists() {
             return Err(Error::FailedLiveVMSe(format!(
                 "Live VM file already exists at {}",
                 file.display()
             )));
         }
-        let data = toml::to_string_pretty(&self).map_err(|e| Error::FailedLiveVMSe(e.to_string()))?;
+        let data = toml::to_string_pretty(self).map_err(|e| Error::FailedLiveVMSe(e.to_string()))?;
         File::create(file)
             .and_then(|mut file| file.write_all(data.as_bytes()))
             .map_err(|e| Error::FailedLiveVMSe(e.to_string()))?;
@@ -0,0 +1,47 @@
+use toml::{Table, Value};
+
+use crate::{config::CONFIG_VERSION, error::ConfigError};
+
+// Migrations which upgrade a configuration table by one version, starting from version 1
+const MIGRATIONS: [fn(&mut Table); CONFIG_VERSION as usize - 1] = [network_interfaces];
+
+// Upgrade a configuration table written for an older schema in place, returning whether it was changed
+pub(crate) fn upgrade(table: &mut Table) -> Result<bool, ConfigError> {
+    let version = match table.get("version") {
+        None => 1,
+        Some(Value::Integer(version)) => u32::try_from(*version).map_err(|_| ConfigError::UnsupportedVersion(0))?,
+        // Left for deserialization to report
+        Some(_) => return Ok(false),
+    };
+    if version == CONFIG_VERSION {
+        return Ok(false);
+    } else if version == 0 || version > CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(table);
+    }
+    table.insert("version".into(), Value::Integer(CONFIG_VERSION.into()));
+    Ok(true)
+}
+
+// Version 2 moved the single network interface, whose settings were held directly in `network`, into a list
+fn network_interfaces(table: &mut Table) {
+    const INTERFACE_KEYS: [&str; 8] = ["type", "port_forwards", "ssh_port", "restrict", "bridge", "mac_addr", "macaddr", "MAC Address"];
+    let Some(Value::Table(network)) = table.get_mut("network") else { return };
+    let mut interface = INTERFACE_KEYS
+        .iter()
+        .filter_map(|key| network.remove(*key).map(|value| (key.to_string(), value)))
+        .collect::<Table>();
+    if interface.is_empty() {
+        return;
+    }
+    // The type was optional, defaulting to NAT, but is required in the list
+    interface.entry("type").or_insert_with(|| "nat".into());
+    let interfaces = match interface.get("type").and_then(Value::as_str) {
+        Some("none") => Vec::new(),
+        _ => vec![Value::Table(interface)],
+    };
+    network.insert("interfaces".into(), Value::Array(interfaces));
+}
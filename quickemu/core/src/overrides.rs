@@ -0,0 +1,241 @@
+use std::{fmt, str::FromStr};
+
+use toml::{Table, Value};
+
+use crate::error::ConfigError;
+
+const ENV_PREFIX: &str = "QUICKEMU_";
+
+// Settings which can be set through environment variables, which are named after the key path, e.g. `QUICKEMU_IO_DISPLAY_SPICE_PORT`
+// sets `io.display.spice_port`. Settings within arrays of tables, such as images and network interfaces, can only be set with `--set`
+const ENV_KEYS: &[(&str, EnvValue)] = &[
+    ("vm_dir", EnvValue::Scalar),
+    ("vm_name", EnvValue::Scalar),
+    ("guest_agent", EnvValue::Scalar),
+    ("extra_args", EnvValue::Array),
+    ("guest.os", EnvValue::Scalar),
+    ("guest.release", EnvValue::Scalar),
+    ("machine.arch", EnvValue::Scalar),
+    ("machine.machine", EnvValue::Scalar),
+    ("machine.cpu_threads", EnvValue::Scalar),
+    ("machine.boot.type", EnvValue::Scalar),
+    ("machine.boot.secure_boot", EnvValue::Scalar),
+    ("machine.tpm", EnvValue::Scalar),
+    ("machine.ram", EnvValue::Scalar),
+    ("machine.status_quo", EnvValue::Scalar),
+    ("machine.watchdog", EnvValue::Scalar),
+    ("network.monitor.type", EnvValue::Scalar),
+    ("network.monitor.address", EnvValue::Scalar),
+    ("network.monitor.socketpath", EnvValue::Scalar),
+    ("network.serial.type", EnvValue::Scalar),
+    ("network.serial.address", EnvValue::Scalar),
+    ("network.serial.socketpath", EnvValue::Scalar),
+    ("io.usb_controller", EnvValue::Scalar),
+    ("io.keyboard", EnvValue::Scalar),
+    ("io.keyboard_layout", EnvValue::Scalar),
+    ("io.mouse", EnvValue::Scalar),
+    ("io.soundcard", EnvValue::Scalar),
+    ("io.public_dir", EnvValue::Scalar),
+    ("io.display.type", EnvValue::Scalar),
+    ("io.display.access", EnvValue::Scalar),
+    ("io.display.viewer", EnvValue::Scalar),
+    ("io.display.spice_port", EnvValue::Scalar),
+    ("io.display.accelerated", EnvValue::Scalar),
+    ("io.display.braille", EnvValue::Scalar),
+    ("io.display.resolution.type", EnvValue::Scalar),
+    ("io.display.resolution.width", EnvValue::Scalar),
+    ("io.display.resolution.height", EnvValue::Scalar),
+    ("io.display.resolution.display_name", EnvValue::Scalar),
+    ("io.display.resolution.percentage", EnvValue::Float),
+    ("restart.policy", EnvValue::Scalar),
+    ("restart.max_retries", EnvValue::Scalar),
+    ("restart.backoff", EnvValue::Scalar),
+    ("restart.max_backoff", EnvValue::Scalar),
+    ("hooks.pre_start", EnvValue::Array),
+    ("hooks.post_start", EnvValue::Array),
+    ("hooks.post_stop", EnvValue::Array),
+];
+
+// How an environment variable's value is read. Unlike `--set`, values aren't parsed as TOML unless the setting is an array,
+// so that e.g. a VM named `inf` isn't read as a float
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EnvValue {
+    // An integer, a boolean, or otherwise a string
+    Scalar,
+    // A float, or otherwise a string
+    Float,
+    // A TOML array, or otherwise a string
+    Array,
+}
+
+impl EnvValue {
+    fn parse(self, value: &str) -> Value {
+        match self {
+            Self::Scalar => value
+                .parse::<i64>()
+                .map(Value::Integer)
+                .or_else(|_| value.parse::<bool>().map(Value::Boolean))
+                .unwrap_or_else(|_| Value::String(value.to_string())),
+            Self::Float => value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .map_or_else(|| Value::String(value.to_string()), Value::Float),
+            Self::Array => parse_toml_value(value),
+        }
+    }
+}
+
+// A single setting, applied over the configuration file before it's deserialized
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    // Dotted TOML key path, e.g. `machine.ram` or `images.iso[0].path`
+    pub key: String,
+    pub value: Value,
+}
+
+impl Override {
+    // Values are read as TOML where possible (numbers, booleans, arrays), and otherwise as strings
+    pub fn new(key: impl Into<String>, value: &str) -> Self {
+        Self {
+            key: key.into(),
+            value: parse_toml_value(value),
+        }
+    }
+
+    fn apply(&self, table: &mut Table) -> Result<(), ConfigError> {
+        let invalid = || ConfigError::InvalidOverride(self.key.clone());
+        let parts = self
+            .key
+            .split('.')
+            .map(|part| parse_part(part).ok_or_else(invalid))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ((name, index), parents) = parts.split_last().ok_or_else(invalid)?;
+
+        let mut table = table;
+        for (name, index) in parents {
+            let entry = table.entry(*name).or_insert_with(|| match index {
+                Some(_) => Value::Array(Vec::new()),
+                None => Value::Table(Table::new()),
+            });
+            let entry = match index {
+                Some(index) => array_entry(entry, *index, || Value::Table(Table::new())).ok_or_else(invalid)?,
+                None => entry,
+            };
+            table = entry.as_table_mut().ok_or_else(invalid)?;
+        }
+        match index {
+            Some(index) => {
+                let entry = table.entry(*name).or_insert_with(|| Value::Array(Vec::new()));
+                *array_entry(entry, *index, || Value::Boolean(false)).ok_or_else(invalid)? = self.value.clone();
+            }
+            None => {
+                table.insert(name.to_string(), self.value.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Override {
+    type Err = ConfigError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok(Self::new(key.trim(), value.trim())),
+            _ => Err(ConfigError::InvalidOverride(input.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Override {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+// Overrides are applied in order, after merging defaults and includes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overrides(Vec<Override>);
+
+impl Overrides {
+    // Read overrides from `QUICKEMU_*` environment variables. Variables which don't name a known setting are ignored with a warning
+    pub fn from_env() -> Self {
+        let mut overrides = std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .filter_map(|(name, value)| {
+                let suffix = name.strip_prefix(ENV_PREFIX)?;
+                match env_key(suffix) {
+                    Some((key, kind)) => Some(Override {
+                        key: key.to_string(),
+                        value: kind.parse(&value),
+                    }),
+                    None => {
+                        log::warn!("Ignoring {name}, which does not match a configuration setting");
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        overrides.sort_by(|a, b| a.key.cmp(&b.key));
+        Self(overrides)
+    }
+
+    pub fn push(&mut self, value: Override) {
+        self.0.push(value);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Override> {
+        self.0.iter()
+    }
+
+    pub(crate) fn apply(&self, table: &mut Table) -> Result<(), ConfigError> {
+        self.0.iter().try_for_each(|value| value.apply(table))
+    }
+}
+
+impl FromIterator<Override> for Overrides {
+    fn from_iter<T: IntoIterator<Item = Override>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Extend<Override> for Overrides {
+    fn extend<T: IntoIterator<Item = Override>>(&mut self, iter: T) {
+        self.0.extend(iter);
+    }
+}
+
+// Find the setting an environment variable (without its prefix) refers to
+fn env_key(name: &str) -> Option<(&'static str, EnvValue)> {
+    let name = name.to_lowercase();
+    ENV_KEYS.iter().copied().find(|(key, _)| key.replace('.', "_") == name)
+}
+
+fn parse_toml_value(value: &str) -> Value {
+    format!("value = {value}")
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_string()))
+}
+
+fn parse_part(part: &str) -> Option<(&str, Option<usize>)> {
+    let (name, index) = match part.split_once('[') {
+        Some((name, index)) => (name, Some(index.strip_suffix(']')?.parse().ok()?)),
+        None => (part, None),
+    };
+    (!name.is_empty()).then_some((name, index))
+}
+
+// Arrays may be extended by one element, by referencing the index just past their end
+fn array_entry(value: &mut Value, index: usize, new: impl FnOnce() -> Value) -> Option<&mut Value> {
+    let array = value.as_array_mut()?;
+    if index == array.len() {
+        array.push(new());
+    }
+    array.get_mut(index)
+}
@@ -0,0 +1,112 @@
+use std::{borrow::Cow, ffi::OsStr, fmt, path::PathBuf};
+
+use serde::Serialize;
+use size::Size;
+
+use crate::{config::QemuArgs, fl};
+
+// A side effect which launching the VM would cause, reported instead of performed in a dry run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlannedAction {
+    CreateDisk { path: PathBuf, size: u64, format: String },
+    CopyOvmfVars { from: PathBuf, to: PathBuf },
+    StartSwtpm { binary: PathBuf, socket: PathBuf },
+    LaunchViewer { viewer: &'static str, port: u16 },
+    SendMonitorCommands { commands: Vec<String> },
+}
+
+impl fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::CreateDisk { path, size, format } => fl!(
+                "plan-create-disk",
+                path = path.display().to_string(),
+                size = Size::from_bytes(*size).to_string(),
+                format = format
+            ),
+            Self::CopyOvmfVars { from, to } => fl!(
+                "plan-copy-ovmf-vars",
+                from = from.display().to_string(),
+                to = to.display().to_string()
+            ),
+            Self::StartSwtpm { binary, socket } => fl!(
+                "plan-start-swtpm",
+                binary = binary.display().to_string(),
+                socket = socket.display().to_string()
+            ),
+            Self::LaunchViewer { viewer, port } => {
+                let viewer = *viewer;
+                fl!("plan-launch-viewer", viewer = viewer, port = port)
+            }
+            Self::SendMonitorCommands { commands } => fl!("plan-send-monitor-commands", commands = commands.join(", ")),
+        };
+        f.write_str(&text)
+    }
+}
+
+#[derive(Debug)]
+pub struct LaunchPlan {
+    pub qemu_bin: &'static str,
+    pub qemu_args: QemuArgs,
+}
+
+impl LaunchPlan {
+    pub fn actions(&self) -> &[PlannedAction] {
+        &self.qemu_args.planned_actions
+    }
+
+    // The command which would be run, quoted for POSIX shells
+    pub fn command_line(&self) -> String {
+        std::iter::once(shell_quote(OsStr::new(self.qemu_bin)))
+            .chain(self.qemu_args.qemu_args.iter().map(|arg| shell_quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn to_json(&self) -> String {
+        let plan = JsonPlan {
+            binary: self.qemu_bin,
+            args: self.qemu_args.qemu_args.iter().map(|arg| arg.to_string_lossy()).collect(),
+            command_line: self.command_line(),
+            actions: self.actions(),
+            warnings: self.qemu_args.warnings.iter().map(ToString::to_string).collect(),
+            display: self
+                .qemu_args
+                .display
+                .iter()
+                .map(|display| JsonDisplay {
+                    name: &display.name,
+                    value: &display.value,
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&plan).expect("Launch plan should be serializable")
+    }
+}
+
+#[derive(Serialize)]
+struct JsonPlan<'a> {
+    binary: &'a str,
+    args: Vec<Cow<'a, str>>,
+    command_line: String,
+    actions: &'a [PlannedAction],
+    warnings: Vec<String>,
+    display: Vec<JsonDisplay<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonDisplay<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+pub fn shell_quote(arg: &OsStr) -> Cow<'_, str> {
+    let arg = arg.to_string_lossy();
+    let safe = |b: u8| b.is_ascii_alphanumeric() || b"-_./=,:+@%".contains(&b);
+    if !arg.is_empty() && arg.bytes().all(safe) {
+        arg
+    } else {
+        Cow::Owned(format!("'{}'", arg.replace('\'', r"'\''")))
+    }
+}
@@ -0,0 +1,442 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::MonitorError;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct QmpClient {
+    stream: QmpStream,
+    greeting: QmpGreeting,
+    responses: Receiver<QmpResponse>,
+    events: Receiver<QmpEvent>,
+    next_id: u64,
+    timeout: Option<Duration>,
+}
+
+impl QmpClient {
+    pub fn connect_tcp(address: &SocketAddr) -> Result<Self, MonitorError> {
+        let stream = TcpStream::connect_timeout(address, DEFAULT_TIMEOUT).map_err(read_error)?;
+        Self::negotiate(QmpStream::Tcp(stream))
+    }
+
+    #[cfg(unix)]
+    pub fn connect_unix(socketpath: &Path) -> Result<Self, MonitorError> {
+        let stream = UnixStream::connect(socketpath).map_err(read_error)?;
+        Self::negotiate(QmpStream::Unix(stream))
+    }
+
+    fn negotiate(stream: QmpStream) -> Result<Self, MonitorError> {
+        let mut reader = BufReader::new(stream.try_clone().map_err(MonitorError::Read)?);
+
+        // QEMU only greets one client at a time; while another holds the monitor, the greeting never arrives.
+        // The timeout is cleared afterwards, as the reader thread waits for events indefinitely
+        let mut line = String::new();
+        stream.set_read_timeout(Some(DEFAULT_TIMEOUT)).map_err(MonitorError::Read)?;
+        reader.read_line(&mut line).map_err(read_error)?;
+        stream.set_read_timeout(None).map_err(MonitorError::Read)?;
+        let greeting = match serde_json::from_str(&line) {
+            Ok(QmpMessage::Greeting { qmp }) => qmp,
+            _ => return Err(MonitorError::QmpHandshake(line.trim().to_string())),
+        };
+
+        let (response_tx, responses) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        thread::spawn(move || read_messages(reader, response_tx, event_tx));
+
+        let mut client = Self {
+            stream,
+            greeting,
+            responses,
+            events,
+            next_id: 0,
+            timeout: Some(DEFAULT_TIMEOUT),
+        };
+        client.execute(&QmpCapabilities {})?;
+        Ok(client)
+    }
+
+    pub fn greeting(&self) -> &QmpGreeting {
+        &self.greeting
+    }
+
+    // Sets how long to wait for a command's response. `None` waits indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    pub fn execute<C: QmpCommand>(&mut self, command: &C) -> Result<C::Response, MonitorError> {
+        let arguments = serde_json::to_value(command).map_err(|e| MonitorError::QmpDeserialize(e.to_string()))?;
+        let value = self.execute_raw(C::NAME, arguments)?;
+        serde_json::from_value(value).map_err(|e| MonitorError::QmpDeserialize(e.to_string()))
+    }
+
+    pub fn execute_raw(&mut self, command: &str, arguments: Value) -> Result<Value, MonitorError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let request = QmpRequest {
+            execute: command,
+            arguments: match arguments {
+                Value::Null => None,
+                Value::Object(ref map) if map.is_empty() => None,
+                arguments => Some(arguments),
+            },
+            id,
+        };
+        let mut data = serde_json::to_vec(&request).map_err(|e| MonitorError::QmpDeserialize(e.to_string()))?;
+        data.extend_from_slice(b"\r\n");
+        self.stream.write_all(&data).map_err(MonitorError::Write)?;
+
+        loop {
+            let response = match self.timeout {
+                Some(timeout) => self.responses.recv_timeout(timeout).map_err(|e| match e {
+                    RecvTimeoutError::Timeout => MonitorError::QmpTimeout,
+                    RecvTimeoutError::Disconnected => MonitorError::QmpClosed,
+                })?,
+                None => self.responses.recv().map_err(|_| MonitorError::QmpClosed)?,
+            };
+            // Responses to earlier commands which timed out may still arrive; they are discarded.
+            if response.id.is_some_and(|response_id| response_id != id) {
+                continue;
+            }
+            return response
+                .result
+                .map_err(|QmpErrorDesc { class, desc }| MonitorError::QmpCommand { class, desc });
+        }
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = QmpEvent> + '_ {
+        self.events.try_iter()
+    }
+
+    pub fn wait_event(&self, timeout: Duration) -> Result<QmpEvent, MonitorError> {
+        self.events.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => MonitorError::QmpTimeout,
+            RecvTimeoutError::Disconnected => MonitorError::QmpClosed,
+        })
+    }
+}
+
+impl Drop for QmpClient {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown();
+    }
+}
+
+impl std::fmt::Debug for QmpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QmpClient")
+            .field("greeting", &self.greeting)
+            .field("next_id", &self.next_id)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+fn read_messages(reader: BufReader<QmpStream>, responses: Sender<QmpResponse>, events: Sender<QmpEvent>) {
+    for line in reader.lines() {
+        let Ok(line) = line else { break };
+        match serde_json::from_str(&line) {
+            Ok(QmpMessage::Return { ret, id }) => {
+                let _ = responses.send(QmpResponse { id, result: Ok(ret) });
+            }
+            Ok(QmpMessage::Error { error, id }) => {
+                let _ = responses.send(QmpResponse { id, result: Err(error) });
+            }
+            Ok(QmpMessage::Event(event)) => {
+                let _ = events.send(event);
+            }
+            Ok(QmpMessage::Greeting { .. }) => log::warn!("Received unexpected QMP greeting"),
+            Err(e) => log::warn!("Could not parse QMP message {line}: {e}"),
+        }
+    }
+}
+
+fn read_error(err: io::Error) -> MonitorError {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => MonitorError::QmpTimeout,
+        _ => MonitorError::Read(err),
+    }
+}
+
+enum QmpStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl QmpStream {
+    fn try_clone(&self) -> std::io::Result<Self> {
+        Ok(match self {
+            Self::Tcp(stream) => Self::Tcp(stream.try_clone()?),
+            #[cfg(unix)]
+            Self::Unix(stream) => Self::Unix(stream.try_clone()?),
+        })
+    }
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+    fn shutdown(&self) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.shutdown(Shutdown::Both),
+        }
+    }
+}
+
+impl Read for QmpStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for QmpStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write(buf),
+        }
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct QmpRequest<'a> {
+    execute: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arguments: Option<Value>,
+    id: u64,
+}
+
+struct QmpResponse {
+    id: Option<u64>,
+    result: Result<Value, QmpErrorDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QmpMessage {
+    Return {
+        #[serde(rename = "return")]
+        ret: Value,
+        id: Option<u64>,
+    },
+    Error {
+        error: QmpErrorDesc,
+        id: Option<u64>,
+    },
+    Event(QmpEvent),
+    Greeting {
+        #[serde(rename = "QMP")]
+        qmp: QmpGreeting,
+    },
+}
+
+#[derive(Deserialize)]
+struct QmpErrorDesc {
+    class: String,
+    desc: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct QmpGreeting {
+    pub version: VersionInfo,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct QmpEvent {
+    pub event: String,
+    #[serde(default)]
+    pub data: Value,
+    pub timestamp: QmpTimestamp,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct QmpTimestamp {
+    pub seconds: i64,
+    pub microseconds: i64,
+}
+
+pub trait QmpCommand: Serialize {
+    const NAME: &'static str;
+    type Response: DeserializeOwned;
+}
+
+// Response of commands which return an empty object
+#[derive(Clone, Debug, Deserialize)]
+pub struct Empty {}
+
+#[derive(Serialize)]
+struct QmpCapabilities {}
+impl QmpCommand for QmpCapabilities {
+    const NAME: &'static str = "qmp_capabilities";
+    type Response = Empty;
+}
+
+#[derive(Serialize)]
+pub struct QueryStatus {}
+impl QmpCommand for QueryStatus {
+    const NAME: &'static str = "query-status";
+    type Response = StatusInfo;
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StatusInfo {
+    pub running: bool,
+    pub status: RunState,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, derive_more::Display)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunState {
+    #[display("debug")]
+    Debug,
+    #[display("inmigrate")]
+    Inmigrate,
+    #[display("internal-error")]
+    InternalError,
+    #[display("io-error")]
+    IoError,
+    #[display("paused")]
+    Paused,
+    #[display("postmigrate")]
+    Postmigrate,
+    #[display("prelaunch")]
+    Prelaunch,
+    #[display("finish-migrate")]
+    FinishMigrate,
+    #[display("restore-vm")]
+    RestoreVm,
+    #[display("running")]
+    Running,
+    #[display("save-vm")]
+    SaveVm,
+    #[display("shutdown")]
+    Shutdown,
+    #[display("suspended")]
+    Suspended,
+    #[display("watchdog")]
+    Watchdog,
+    #[display("guest-panicked")]
+    GuestPanicked,
+    #[display("colo")]
+    Colo,
+    #[serde(other)]
+    #[display("unknown")]
+    Unknown,
+}
+
+#[derive(Serialize)]
+pub struct QueryVersion {}
+impl QmpCommand for QueryVersion {
+    const NAME: &'static str = "query-version";
+    type Response = VersionInfo;
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct VersionInfo {
+    pub qemu: QemuVersion,
+    #[serde(default)]
+    pub package: String,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, derive_more::Display)]
+#[display("{major}.{minor}.{micro}")]
+pub struct QemuVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub micro: u32,
+}
+
+#[derive(Serialize)]
+pub struct SystemPowerdown {}
+impl QmpCommand for SystemPowerdown {
+    const NAME: &'static str = "system_powerdown";
+    type Response = Empty;
+}
+
+#[derive(Serialize)]
+pub struct SystemReset {}
+impl QmpCommand for SystemReset {
+    const NAME: &'static str = "system_reset";
+    type Response = Empty;
+}
+
+#[derive(Serialize)]
+pub struct Stop {}
+impl QmpCommand for Stop {
+    const NAME: &'static str = "stop";
+    type Response = Empty;
+}
+
+#[derive(Serialize)]
+pub struct Cont {}
+impl QmpCommand for Cont {
+    const NAME: &'static str = "cont";
+    type Response = Empty;
+}
+
+#[derive(Serialize)]
+pub struct Quit {}
+impl QmpCommand for Quit {
+    const NAME: &'static str = "quit";
+    type Response = Empty;
+}
+
+#[derive(Serialize)]
+pub struct HumanMonitorCommand<'a> {
+    #[serde(rename = "command-line")]
+    pub command_line: &'a str,
+}
+impl QmpCommand for HumanMonitorCommand<'_> {
+    const NAME: &'static str = "human-monitor-command";
+    type Response = String;
+}
+
+#[derive(Serialize)]
+pub struct SendKey<'a> {
+    pub keys: Vec<KeyValue<'a>>,
+    #[serde(rename = "hold-time", skip_serializing_if = "Option::is_none")]
+    pub hold_time: Option<u32>,
+}
+impl QmpCommand for SendKey<'_> {
+    const NAME: &'static str = "send-key";
+    type Response = Empty;
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum KeyValue<'a> {
+    Number(i64),
+    Qcode(&'a str),
+}
@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+    config::Config,
+    data::{Arch, GuestOS},
+    error::ConfigError,
+    layer,
+    live_vm::LiveVM,
+    overrides::Overrides,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct VmRegistry {
+    dirs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VmInfo {
+    pub name: String,
+    pub config_file: PathBuf,
+    pub vm_dir: PathBuf,
+    pub guest: GuestOS,
+    pub arch: Arch,
+    pub pid: Option<u32>,
+    pub ssh_port: Option<u16>,
+    #[cfg(not(target_os = "macos"))]
+    pub spice_port: Option<u16>,
+    // Bytes allocated on disk by the VM directory
+    pub disk_usage: u64,
+    #[serde(skip)]
+    pub config: Config,
+    #[serde(skip)]
+    pub live_vm: Option<LiveVM>,
+}
+
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub vms: Vec<VmInfo>,
+    // Directories which couldn't be read and configs which couldn't be parsed
+    pub errors: Vec<(PathBuf, ConfigError)>,
+}
+
+impl VmRegistry {
+    pub fn new<P: Into<PathBuf>>(dirs: impl IntoIterator<Item = P>) -> Self {
+        Self {
+            dirs: dirs.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn add_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.dirs.push(dir.into());
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    pub fn scan(&self) -> ScanResult {
+        let mut result = ScanResult::default();
+
+        for dir in &self.dirs {
+            let entries = match std::fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    result.errors.push((dir.clone(), e.into()));
+                    continue;
+                }
+            };
+            let mut config_files = entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+                .collect::<Vec<_>>();
+            config_files.sort();
+
+            for config_file in config_files {
+                if !is_quickemu_config(&config_file) {
+                    continue;
+                }
+                match VmInfo::read(&config_file) {
+                    Ok((info, live_vm_error)) => {
+                        if let Some(e) = live_vm_error {
+                            result.errors.push((config_file, e));
+                        }
+                        result.vms.push(info);
+                    }
+                    Err(e) => result.errors.push((config_file, e)),
+                }
+            }
+        }
+
+        result
+    }
+
+    // Find a VM by name across all directories
+    pub fn find(&self, name: &str) -> Option<VmInfo> {
+        self.scan().vms.into_iter().find(|vm| vm.name == name)
+    }
+}
+
+impl VmInfo {
+    fn read(config_file: &Path) -> Result<(Self, Option<ConfigError>), ConfigError> {
+        let config = Config::read(config_file)?;
+        // A broken live VM file shouldn't hide the VM from the listing
+        let (live_vm, live_vm_error) = match config.live_vm() {
+            Ok(live_vm) => (live_vm, None),
+            Err(e) => (None, Some(e.into())),
+        };
+        let vm_dir = config.vm_dir.clone().expect("VM directory should be set");
+
+        let info = Self {
+            name: config.vm_name.clone(),
+            config_file: config_file.to_path_buf(),
+            disk_usage: disk_usage(&vm_dir),
+            vm_dir,
+            guest: config.guest,
+            arch: config.machine.arch,
+            pid: live_vm.as_ref().map(|vm| vm.pid),
+            ssh_port: live_vm.as_ref().and_then(|vm| vm.ssh_port),
+            #[cfg(not(target_os = "macos"))]
+            spice_port: live_vm.as_ref().and_then(|vm| vm.spice_port),
+            config,
+            live_vm,
+        };
+        Ok((info, live_vm_error))
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.live_vm.is_some()
+    }
+}
+
+// The top-level keys of a configuration, including those only used while reading it
+const CONFIG_KEYS: [&str; 13] = [
+    "version",
+    "include",
+    "vm_dir",
+    "vm_name",
+    "guest",
+    "machine",
+    "images",
+    "network",
+    "io",
+    "restart",
+    "hooks",
+    "guest_agent",
+    "extra_args",
+];
+
+// Other TOML files may live alongside VM configs; quickemu configs always contain a guest table, though it may come from
+// an include or the user's defaults. As a guest in the defaults would otherwise match any TOML file, files relying on it
+// must only contain configuration keys. Files whose includes can't be read are kept, so that the error is reported
+fn is_quickemu_config(file: &Path) -> bool {
+    let Some((contents, table)) = std::fs::read_to_string(file)
+        .ok()
+        .and_then(|contents| contents.parse::<toml::Table>().ok().map(|table| (contents, table)))
+    else {
+        return false;
+    };
+    let layered = table.contains_key("guest") || table.contains_key("include");
+    let config_keys_only = !table.is_empty() && table.keys().all(|key| CONFIG_KEYS.contains(&key.as_str()));
+    match layer::load(&contents, file, &Overrides::default()) {
+        Ok(Some(merged)) => merged.contains_key("guest") && (layered || config_keys_only),
+        Ok(None) => table.contains_key("guest"),
+        Err(_) => layered,
+    }
+}
+
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if metadata.is_dir() {
+        std::fs::read_dir(path)
+            .map(|entries| entries.filter_map(Result::ok).map(|entry| disk_usage(&entry.path())).sum())
+            .unwrap_or(0)
+    } else {
+        allocated_size(&metadata)
+    }
+}
+
+// Disk images are usually sparse, so the allocated size is more meaningful than the file length
+#[cfg(unix)]
+fn allocated_size(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+#[cfg(not(unix))]
+fn allocated_size(metadata: &std::fs::Metadata) -> u64 {
+    metadata.len()
+}
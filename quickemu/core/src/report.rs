@@ -0,0 +1,110 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::Config,
+    data::{MonitorArg, MonitorInner, PortForward},
+};
+
+// A machine-readable description of a launched VM, holding what `LaunchResult::display` shows to people.
+// Paths are absolute, so that tools don't need to know the working directory quickemu was run from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LaunchReport {
+    pub vm_name: String,
+    pub vm_dir: PathBuf,
+    pub pid: u32,
+    pub ssh_port: Option<u16>,
+    pub spice_port: Option<u16>,
+    pub port_forwards: Vec<PortForward>,
+    pub monitor: Option<Endpoint>,
+    pub serial: Option<Endpoint>,
+    pub disks: Vec<PathBuf>,
+    pub isos: Vec<PathBuf>,
+    pub tpm_socket: Option<PathBuf>,
+    pub guest_agent_socket: Option<PathBuf>,
+    pub log_file: Option<PathBuf>,
+    pub restarts: u32,
+    pub warnings: Vec<String>,
+}
+
+// Where the monitor or serial port can be reached
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Endpoint {
+    Telnet {
+        address: SocketAddr,
+    },
+    Socket {
+        path: PathBuf,
+    },
+    Qmp {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        address: Option<SocketAddr>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        path: Option<PathBuf>,
+    },
+}
+
+impl Endpoint {
+    fn new<T: MonitorArg>(monitor: &MonitorInner<T>) -> Option<Self> {
+        match monitor {
+            MonitorInner::None => None,
+            MonitorInner::Telnet { address } => Some(Self::Telnet { address: *address.as_ref() }),
+            #[cfg(unix)]
+            MonitorInner::Socket { socketpath } => socketpath.as_deref().map(|path| Self::Socket { path: absolute(path) }),
+            MonitorInner::Qmp { address, .. } => Some(Self::Qmp {
+                address: address.as_ref().map(|address| *address.as_ref()),
+                #[cfg(unix)]
+                path: monitor.socketpath().map(absolute),
+                #[cfg(not(unix))]
+                path: None,
+            }),
+        }
+    }
+}
+
+impl LaunchReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Launch report should be serializable")
+    }
+}
+
+impl Config {
+    // Describe the VM before it's launched. The PID, attached images and warnings are filled in once QEMU has started
+    pub(crate) fn launch_report(&self) -> LaunchReport {
+        let vm_dir = self.vm_dir.as_deref().expect("VM directory should be set");
+        #[cfg(not(target_os = "macos"))]
+        let spice_port = match self.io.display.display_type {
+            crate::data::DisplayType::Spice { spice_port, .. } => Some(spice_port),
+            _ => None,
+        };
+        #[cfg(target_os = "macos")]
+        let spice_port = None;
+
+        LaunchReport {
+            vm_name: self.vm_name.clone(),
+            vm_dir: absolute(vm_dir),
+            pid: 0,
+            ssh_port: self.network.ssh_port(),
+            spice_port,
+            port_forwards: self.network.port_forwards().cloned().collect(),
+            monitor: Endpoint::new(&self.network.monitor),
+            serial: Endpoint::new(&self.network.serial),
+            disks: Vec::new(),
+            isos: Vec::new(),
+            tpm_socket: self.machine.tpm_socket(vm_dir, &self.vm_name).as_deref().map(absolute),
+            guest_agent_socket: self.guest_agent_socket().as_deref().map(absolute),
+            log_file: None,
+            restarts: 0,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+pub(crate) fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
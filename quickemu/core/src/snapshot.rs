@@ -0,0 +1,173 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, data::DiskFormat, error::SnapshotError, live_vm::LiveVM};
+
+// Saving or loading VM state can take a long time with large amounts of RAM
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub date: SystemTime,
+    pub vm_clock: Duration,
+    // Size of the saved VM state. Snapshots taken while the VM was stopped only contain disk state
+    pub vm_state_size: u64,
+}
+
+impl LiveVM {
+    pub fn create_snapshot(&self, name: &str) -> Result<(), SnapshotError> {
+        self.snapshot_cmd("savevm", name)
+    }
+    pub fn revert_snapshot(&self, name: &str) -> Result<(), SnapshotError> {
+        self.snapshot_cmd("loadvm", name)
+    }
+    pub fn delete_snapshot(&self, name: &str) -> Result<(), SnapshotError> {
+        self.snapshot_cmd("delvm", name)
+    }
+    pub fn snapshots(&self) -> Result<Vec<Snapshot>, SnapshotError> {
+        list_snapshots(&self.disks)
+    }
+
+    fn snapshot_cmd(&self, command: &str, name: &str) -> Result<(), SnapshotError> {
+        validate_name(name)?;
+        let response = self
+            .monitor
+            .send_cmd_timeout(&format!("{command} {name}"), SNAPSHOT_TIMEOUT)
+            .map_err(|e| SnapshotError::Monitor(e.to_string()))?;
+        // HMP reports failures as text rather than through a status
+        match response.lines().map(str::trim).find(|line| line.starts_with("Error")) {
+            Some(error) => Err(SnapshotError::Monitor(error.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Config {
+    pub fn create_snapshot(&self, name: &str) -> Result<(), SnapshotError> {
+        match self.live_vm()? {
+            Some(live_vm) => live_vm.create_snapshot(name),
+            None => self.qemu_img_snapshot("-c", name),
+        }
+    }
+    pub fn revert_snapshot(&self, name: &str) -> Result<(), SnapshotError> {
+        match self.live_vm()? {
+            Some(live_vm) => live_vm.revert_snapshot(name),
+            None => self.qemu_img_snapshot("-a", name),
+        }
+    }
+    pub fn delete_snapshot(&self, name: &str) -> Result<(), SnapshotError> {
+        match self.live_vm()? {
+            Some(live_vm) => live_vm.delete_snapshot(name),
+            None => self.qemu_img_snapshot("-d", name),
+        }
+    }
+    pub fn snapshots(&self) -> Result<Vec<Snapshot>, SnapshotError> {
+        match self.live_vm()? {
+            Some(live_vm) => live_vm.snapshots(),
+            None => list_snapshots(&self.disk_paths()),
+        }
+    }
+
+    fn qemu_img_snapshot(&self, operation: &str, name: &str) -> Result<(), SnapshotError> {
+        validate_name(name)?;
+        if self.images.disk.is_empty() {
+            return Err(SnapshotError::NoDisks);
+        }
+        if let Some(disk) = self
+            .images
+            .disk
+            .iter()
+            .find(|disk| !matches!(disk.format, DiskFormat::Qcow2 { .. }))
+        {
+            return Err(SnapshotError::UnsupportedFormat(disk.path.display().to_string()));
+        }
+
+        for disk in self.disk_paths() {
+            #[cfg(not(feature = "inbuilt_commands"))]
+            let mut command = Command::new("qemu-img");
+
+            command.arg("snapshot").arg(operation).arg(name).arg(&disk);
+
+            let output = command.output().map_err(|e| SnapshotError::Command(e.to_string()))?;
+            if !output.status.success() {
+                return Err(SnapshotError::QemuImg(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), SnapshotError> {
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        return Err(SnapshotError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct QemuImgInfo {
+    #[serde(default)]
+    snapshots: Vec<QemuImgSnapshot>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct QemuImgSnapshot {
+    name: String,
+    date_sec: u64,
+    date_nsec: u32,
+    vm_clock_sec: u64,
+    vm_clock_nsec: u32,
+    vm_state_size: u64,
+}
+
+impl From<QemuImgSnapshot> for Snapshot {
+    fn from(snapshot: QemuImgSnapshot) -> Self {
+        Self {
+            name: snapshot.name,
+            date: SystemTime::UNIX_EPOCH + Duration::new(snapshot.date_sec, snapshot.date_nsec),
+            vm_clock: Duration::new(snapshot.vm_clock_sec, snapshot.vm_clock_nsec),
+            vm_state_size: snapshot.vm_state_size,
+        }
+    }
+}
+
+// Like QEMU, only snapshots which are present on every disk are listed
+fn list_snapshots(disks: &[PathBuf]) -> Result<Vec<Snapshot>, SnapshotError> {
+    let mut disk_snapshots = disks.iter().map(|disk| disk_snapshots(disk));
+    let Some(first) = disk_snapshots.next() else {
+        return Err(SnapshotError::NoDisks);
+    };
+    let mut snapshots = first?;
+    for other in disk_snapshots {
+        let other = other?;
+        snapshots.retain(|snapshot| other.iter().any(|s| s.name == snapshot.name));
+    }
+    Ok(snapshots)
+}
+
+fn disk_snapshots(disk: &Path) -> Result<Vec<Snapshot>, SnapshotError> {
+    #[cfg(not(feature = "inbuilt_commands"))]
+    let mut command = Command::new("qemu-img");
+
+    // Sharing is forced so that images of running VMs can be read
+    command.arg("info").arg("--force-share").arg("--output=json").arg(disk);
+
+    let output = command.output().map_err(|e| SnapshotError::Command(e.to_string()))?;
+    if !output.status.success() {
+        return Err(SnapshotError::QemuImg(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    let info: QemuImgInfo = serde_json::from_slice(&output.stdout).map_err(|e| SnapshotError::Deserialize(e.to_string()))?;
+    Ok(info.snapshots.into_iter().map(Snapshot::from).collect())
+}
@@ -0,0 +1,290 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+    process::{Child, ExitStatus},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::Config,
+    data::RestartPolicy,
+    error::{Error, LiveVMError, Warning},
+    hooks::{HookRunner, HookStage},
+    live_vm::{LiveVM, ShutdownMethod},
+    report::LaunchReport,
+};
+
+// Helpers such as swtpm exit on their own once QEMU disconnects. Any still running after this are killed
+const HELPER_EXIT_TIMEOUT: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Watches a launched QEMU process, cleaning up after it once it exits and restarting it according to the VM's restart policy
+#[derive(Debug)]
+pub struct Supervisor {
+    config: Config,
+    vm: RunningVM,
+    restarts: u32,
+    // Set once QEMU has exited and is due to be restarted
+    pending_restart: Option<(Instant, ExitReport)>,
+    stopping: bool,
+}
+
+// A single run of QEMU, along with everything started alongside it
+#[derive(Debug)]
+pub(crate) struct RunningVM {
+    pub(crate) qemu: Child,
+    pub(crate) helpers: Vec<Child>,
+    pub(crate) threads: Vec<JoinHandle<Result<(), Error>>>,
+    pub(crate) live_vm: LiveVM,
+    // The live VM file, pidfile and sockets, which are removed once QEMU exits
+    pub(crate) state_files: Vec<PathBuf>,
+    pub(crate) hooks: HookRunner,
+    pub(crate) report: LaunchReport,
+}
+
+#[derive(Debug)]
+pub struct ExitReport {
+    pub status: ExitStatus,
+    // Errors returned by threads started alongside QEMU, e.g. monitor commands which could not be sent
+    pub errors: Vec<Error>,
+    // Output and failures of post-stop hooks
+    pub warnings: Vec<Warning>,
+    pub restarts: u32,
+}
+
+impl ExitReport {
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+}
+
+impl Supervisor {
+    pub(crate) fn new(config: Config, vm: RunningVM) -> Self {
+        Self {
+            config,
+            vm,
+            restarts: 0,
+            pending_restart: None,
+            stopping: false,
+        }
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.vm.qemu.id()
+    }
+
+    pub fn live_vm(&self) -> &LiveVM {
+        &self.vm.live_vm
+    }
+
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    // Describes the current run of QEMU, which changes when it's restarted
+    pub fn report(&self) -> &LaunchReport {
+        &self.vm.report
+    }
+
+    // Block until QEMU exits without being restarted, then clean up after it
+    pub fn wait(mut self) -> Result<ExitReport, Error> {
+        loop {
+            if let Some(report) = self.try_wait()? {
+                return Ok(report);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    // Returns a report once QEMU has exited and will not be restarted. Restarts happen during calls to this function
+    pub fn try_wait(&mut self) -> Result<Option<ExitReport>, Error> {
+        if let Some((restart_at, mut report)) = self.pending_restart.take() {
+            if self.stopping {
+                return Ok(Some(report));
+            }
+            if Instant::now() < restart_at {
+                self.pending_restart = Some((restart_at, report));
+                return Ok(None);
+            }
+            self.restarts += 1;
+            let (vm, _, warnings) = match self.config.clone().spawn(false, self.restarts) {
+                Ok(spawned) => spawned,
+                // A failed restart counts towards the retry limit, and is retried with the same backoff as an exit.
+                // The error is only returned once no retries are left
+                Err(err) => {
+                    report.restarts = self.restarts;
+                    return Ok(match self.restart_delay(&report) {
+                        Some(delay) => {
+                            log::warn!("Could not restart QEMU: {err}, retrying in {}s", delay.as_secs());
+                            self.pending_restart = Some((Instant::now() + delay, report));
+                            None
+                        }
+                        None => {
+                            report.errors.push(err);
+                            Some(report)
+                        }
+                    });
+                }
+            };
+            // Other warnings were already shown when the VM was first launched
+            warnings.iter().for_each(|warning| match warning {
+                Warning::HookOutput(..) | Warning::HookFailed(..) => log::warn!("{warning}"),
+                _ => log::debug!("{warning}"),
+            });
+            self.vm = vm;
+            log::info!("Restarted QEMU (PID {}), restart {}", self.pid(), self.restarts);
+            return Ok(None);
+        }
+
+        let Some(status) = self.vm.qemu.try_wait().map_err(|e| Error::Supervise(e.to_string()))? else {
+            return Ok(None);
+        };
+        let stop_requested = self.stopping || self.vm.live_vm.stop_requested();
+        let mut report = self.vm.cleanup(status, self.restarts);
+        report.warnings = self
+            .vm
+            .hooks
+            .run(HookStage::PostStop, Some(self.pid()), Some(status), self.restarts)?;
+        match self.restart_delay(&report).filter(|_| !stop_requested) {
+            Some(delay) => {
+                report.errors.iter().for_each(|err| log::warn!("{err}"));
+                report.warnings.iter().for_each(|warning| log::warn!("{warning}"));
+                log::warn!("QEMU exited with {status}, restarting in {}s", delay.as_secs());
+                self.pending_restart = Some((Instant::now() + delay, report));
+                Ok(None)
+            }
+            None => Ok(Some(report)),
+        }
+    }
+
+    // Ask the VM to shut down, as `LiveVM::shutdown` does, without restarting it. Its exit is still collected through `wait` or `try_wait`.
+    // Returns `None` if QEMU had already exited and was waiting to be restarted
+    pub fn stop(&mut self, timeout: Duration) -> Result<Option<ShutdownMethod>, LiveVMError> {
+        self.stopping = true;
+        match self.pending_restart {
+            Some(_) => Ok(None),
+            None => self.vm.live_vm.shutdown(timeout).map(Some),
+        }
+    }
+
+    // Stop watching the VM, leaving QEMU and its helpers running. Threads only live for the start of the VM, so they're still joined
+    pub fn detach(self) -> Vec<Error> {
+        join_threads(self.vm.threads)
+    }
+
+    fn restart_delay(&self, report: &ExitReport) -> Option<Duration> {
+        let restart = &self.config.restart;
+        let due = match restart.policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !report.success(),
+            RestartPolicy::Always => true,
+        };
+        let retries_left = restart.max_retries.is_none_or(|max_retries| self.restarts < max_retries);
+        (due && retries_left).then(|| {
+            let backoff = restart.backoff.saturating_mul(1 << self.restarts.min(16));
+            Duration::from_secs(backoff.min(restart.max_backoff))
+        })
+    }
+}
+
+impl RunningVM {
+    fn cleanup(&mut self, status: ExitStatus, restarts: u32) -> ExitReport {
+        log::debug!("QEMU (PID {}) exited with {status}", self.qemu.id());
+        stop_helpers(&mut self.helpers);
+        remove_state_files(&mut self.state_files);
+
+        ExitReport {
+            status,
+            errors: join_threads(std::mem::take(&mut self.threads)),
+            warnings: Vec::new(),
+            restarts,
+        }
+    }
+}
+
+// Holds everything started while launching QEMU. If the launch fails part way, dropping the guard kills QEMU
+// and its helpers and removes the VM's state files, as the supervisor would once QEMU exits
+pub(crate) struct SpawnGuard {
+    pub(crate) qemu: Option<Child>,
+    pub(crate) helpers: Vec<Child>,
+    pub(crate) threads: Vec<JoinHandle<Result<(), Error>>>,
+    pub(crate) state_files: Vec<PathBuf>,
+}
+
+impl SpawnGuard {
+    pub(crate) fn new(state_files: Vec<PathBuf>) -> Self {
+        Self {
+            qemu: None,
+            helpers: Vec::new(),
+            threads: Vec::new(),
+            state_files,
+        }
+    }
+
+    // Hand everything over to the VM's supervisor once the launch has succeeded
+    pub(crate) fn into_running(mut self, live_vm: LiveVM, hooks: HookRunner, report: LaunchReport) -> RunningVM {
+        RunningVM {
+            qemu: self.qemu.take().expect("QEMU should have been spawned"),
+            helpers: std::mem::take(&mut self.helpers),
+            threads: std::mem::take(&mut self.threads),
+            live_vm,
+            state_files: std::mem::take(&mut self.state_files),
+            hooks,
+            report,
+        }
+    }
+}
+
+impl Drop for SpawnGuard {
+    fn drop(&mut self) {
+        if let Some(qemu) = &mut self.qemu {
+            log::debug!("Killing QEMU (PID {}) after a failed launch", qemu.id());
+            if let Err(e) = qemu.kill() {
+                log::warn!("Could not kill QEMU (PID {}): {e}", qemu.id());
+            }
+            let _ = qemu.wait();
+        }
+        stop_helpers(&mut self.helpers);
+        remove_state_files(&mut self.state_files);
+        // Threads are left to finish on their own, as they may be blocked on a connection to QEMU
+    }
+}
+
+fn stop_helpers(helpers: &mut Vec<Child>) {
+    let deadline = Instant::now() + HELPER_EXIT_TIMEOUT;
+    for helper in helpers.iter_mut() {
+        while matches!(helper.try_wait(), Ok(None)) && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+        if let Ok(None) = helper.try_wait() {
+            log::debug!("Killing helper process {}", helper.id());
+            if let Err(e) = helper.kill() {
+                log::warn!("Could not kill helper process {}: {e}", helper.id());
+            }
+        }
+        let _ = helper.wait();
+    }
+    helpers.clear();
+}
+
+fn remove_state_files(state_files: &mut Vec<PathBuf>) {
+    for file in state_files.drain(..) {
+        match fs::remove_file(&file) {
+            Ok(()) => log::debug!("Removed {}", file.display()),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Could not remove {}: {e}", file.display()),
+        }
+    }
+}
+
+fn join_threads(threads: Vec<JoinHandle<Result<(), Error>>>) -> Vec<Error> {
+    threads
+        .into_iter()
+        .filter_map(|thread| match thread.join() {
+            Ok(result) => result.err(),
+            Err(_) => Some(Error::Supervise("A launch thread panicked".to_string())),
+        })
+        .collect()
+}
@@ -0,0 +1,351 @@
+use std::{
+    ffi::OsStr,
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    config::Config,
+    data::{DisplayType, MonitorInner, Restart, RestartPolicy},
+    error::{Error, Warning},
+    overrides::Overrides,
+};
+
+// Extra time systemd allows for `ExecStop`, beyond the time given to the guest to shut down, before killing QEMU itself
+const STOP_TIMEOUT_MARGIN: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitScope {
+    User,
+    System,
+}
+
+#[derive(Debug, Clone)]
+pub struct UnitOptions {
+    pub scope: UnitScope,
+    // The quickemu binary and configuration file `ExecStop` uses to shut the VM down. Both should be absolute paths
+    pub quickemu: PathBuf,
+    pub config_file: PathBuf,
+    // Passed to `ExecStop`, so that it reaches the monitor the VM was started with
+    pub overrides: Overrides,
+    // How long the guest is given to shut down before it's terminated
+    pub stop_timeout: Duration,
+}
+
+impl UnitOptions {
+    pub fn new(scope: UnitScope, quickemu: &Path, config_file: &Path) -> Self {
+        Self {
+            scope,
+            quickemu: quickemu.to_path_buf(),
+            config_file: config_file.to_path_buf(),
+            overrides: Overrides::default(),
+            stop_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SystemdUnit {
+    // The unit's file name, e.g. `quickemu-ubuntu.service`
+    pub name: String,
+    pub contents: String,
+    pub warnings: Vec<Warning>,
+}
+
+impl SystemdUnit {
+    // Where systemd looks for units of the given scope
+    pub fn install_path(&self, scope: UnitScope) -> Option<PathBuf> {
+        let dir = match scope {
+            UnitScope::User => dirs::config_dir()?.join("systemd").join("user"),
+            UnitScope::System => PathBuf::from("/etc/systemd/system"),
+        };
+        Some(dir.join(&self.name))
+    }
+}
+
+impl Config {
+    // Build a service which runs QEMU in the foreground with the same arguments `to_qemu_args` produces.
+    // Disks are not created by the service, so the VM must have been launched through quickemu beforehand.
+    // Anything quickemu decides at launch is fixed when the unit is generated: SSH and SPICE ports are taken from
+    // those free at the time, and unix private networks always listen, so the unit should be regenerated if they change
+    pub fn systemd_unit(self, options: &UnitOptions) -> Result<SystemdUnit, Error> {
+        if let Some(disk) = self.disk_paths().into_iter().find(|disk| !disk.exists()) {
+            return Err(Error::UnitMissingDisk(disk.display().to_string()));
+        }
+        let vm_dir = self.vm_dir.clone().expect("VM directory should be set");
+        let vm_name = match self.vm_name.is_empty() {
+            true => vm_dir.file_name().expect("Filename should exist").to_string_lossy().to_string(),
+            false => self.vm_name.clone(),
+        };
+        let qemu_bin = self.machine.arch.qemu_binary();
+        let qemu_bin = which::which(qemu_bin).map_err(|_| Error::QemuNotFound(qemu_bin))?;
+        let swtpm = self.machine.swtpm_daemon_command(&vm_dir, &vm_name)?;
+        let restart = self.restart.clone();
+
+        let mut warnings = Vec::new();
+        match self.io.display.display_type {
+            DisplayType::None => {}
+            #[cfg(not(target_os = "macos"))]
+            DisplayType::Spice { .. } => {}
+            display => warnings.push(Warning::UnitDisplay(display.to_string())),
+        }
+        if matches!(self.network.monitor, MonitorInner::None) {
+            warnings.push(Warning::UnitNoMonitor);
+        }
+        if !self.hooks.is_empty() {
+            warnings.push(Warning::UnitHooks);
+        }
+
+        let (args, arg_warnings) = self.to_qemu_args()?;
+        warnings.extend(arg_warnings);
+
+        let service = ServiceCommands {
+            start_pre: swtpm.map(|swtpm| command_line(swtpm.iter().map(|arg| arg.as_os_str()))),
+            start: command_line(std::iter::once(qemu_bin.as_os_str()).chain(args.iter().map(|arg| arg.as_ref()))),
+            stop: stop_command(options),
+        };
+        Ok(SystemdUnit {
+            name: format!("quickemu-{}.service", escape_unit_name(&vm_name)),
+            contents: unit_contents(&vm_name, &vm_dir, &service, &restart, options),
+            warnings,
+        })
+    }
+}
+
+struct ServiceCommands {
+    start_pre: Option<String>,
+    start: String,
+    stop: String,
+}
+
+fn unit_contents(vm_name: &str, vm_dir: &Path, service: &ServiceCommands, restart: &Restart, options: &UnitOptions) -> String {
+    let mut unit = vec![
+        "# Generated by quickemu-rs. SSH and SPICE ports, and whether unix private networks listen or connect,".to_string(),
+        "# were chosen when this unit was generated. Regenerate it if they change".to_string(),
+        "[Unit]".to_string(),
+        format!("Description=QEMU virtual machine {vm_name}"),
+        "After=network-online.target".to_string(),
+        "Wants=network-online.target".to_string(),
+    ];
+    if let Some(max_retries) = restart.max_retries.filter(|_| restart.policy != RestartPolicy::Never) {
+        unit.push("StartLimitIntervalSec=infinity".to_string());
+        unit.push(format!("StartLimitBurst={}", max_retries.saturating_add(1)));
+    }
+
+    unit.extend([
+        String::new(),
+        "[Service]".to_string(),
+        "Type=simple".to_string(),
+        format!("WorkingDirectory={}", quote(vm_dir.as_os_str())),
+    ]);
+    if let Some(start_pre) = &service.start_pre {
+        unit.push(format!("ExecStartPre={start_pre}"));
+    }
+    unit.extend([
+        format!("ExecStart={}", service.start),
+        format!("ExecStop={}", service.stop),
+        format!("TimeoutStopSec={}", (options.stop_timeout + STOP_TIMEOUT_MARGIN).as_secs()),
+    ]);
+    let policy = match restart.policy {
+        RestartPolicy::Never => "no",
+        RestartPolicy::OnFailure => "on-failure",
+        RestartPolicy::Always => "always",
+    };
+    unit.push(format!("Restart={policy}"));
+    if restart.policy != RestartPolicy::Never {
+        unit.push(format!("RestartSec={}", restart.backoff));
+    }
+
+    let target = match options.scope {
+        UnitScope::User => "default.target",
+        UnitScope::System => "multi-user.target",
+    };
+    unit.extend([String::new(), "[Install]".to_string(), format!("WantedBy={target}"), String::new()]);
+    unit.join("\n")
+}
+
+// systemd provides the PID of QEMU as $MAINPID, which `stop --pid` uses in place of the live VM file quickemu would have written
+fn stop_command(options: &UnitOptions) -> String {
+    let mut command = vec![quote(options.quickemu.as_os_str()), "--vm".to_string(), quote(options.config_file.as_os_str())];
+    for value in options.overrides.iter() {
+        command.push("--set".to_string());
+        command.push(quote(OsStr::new(&value.to_string())));
+    }
+    command.extend(["stop".to_string(), "--pid".to_string(), "$MAINPID".to_string()]);
+    command.extend(["--timeout".to_string(), options.stop_timeout.as_secs().to_string()]);
+    command.join(" ")
+}
+
+fn command_line<'a>(args: impl Iterator<Item = &'a OsStr>) -> String {
+    args.map(quote).collect::<Vec<_>>().join(" ")
+}
+
+// Quote an argument for systemd's command line syntax, escaping its specifiers and variable expansion
+fn quote(arg: &OsStr) -> String {
+    let arg = arg.to_string_lossy().replace('%', "%%").replace('$', "$$");
+    let plain = !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'));
+    match plain {
+        true => arg,
+        false => format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
+
+// Escape characters which aren't allowed in unit names, as `systemd-escape` does
+fn escape_unit_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for (i, byte) in name.bytes().enumerate() {
+        match byte {
+            b'/' => escaped.push('-'),
+            b'.' if i == 0 => escaped.push_str("\\x2e"),
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b':' | b'_' | b'.' => escaped.push(byte as char),
+            _ => {
+                let _ = write!(escaped, "\\x{byte:02x}");
+            }
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> UnitOptions {
+        UnitOptions::new(
+            UnitScope::User,
+            Path::new("/usr/bin/quickemu-rs"),
+            Path::new("/vms/ubuntu.toml"),
+        )
+    }
+
+    fn service() -> ServiceCommands {
+        ServiceCommands {
+            start_pre: None,
+            start: "/usr/bin/qemu-system-x86_64 -name ubuntu".to_string(),
+            stop: stop_command(&options()),
+        }
+    }
+
+    fn restart(policy: RestartPolicy, max_retries: Option<u32>) -> Restart {
+        Restart {
+            policy,
+            max_retries,
+            backoff: 5,
+            ..Restart::default()
+        }
+    }
+
+    fn lines(contents: &str) -> Vec<&str> {
+        contents.lines().collect()
+    }
+
+    #[test]
+    fn unit_contents_sections() {
+        let contents = unit_contents("ubuntu", Path::new("/vms/ubuntu"), &service(), &Restart::default(), &options());
+        let body = contents.lines().skip_while(|line| line.starts_with('#')).collect::<Vec<_>>();
+        assert_eq!(
+            body,
+            [
+                "[Unit]",
+                "Description=QEMU virtual machine ubuntu",
+                "After=network-online.target",
+                "Wants=network-online.target",
+                "",
+                "[Service]",
+                "Type=simple",
+                "WorkingDirectory=/vms/ubuntu",
+                "ExecStart=/usr/bin/qemu-system-x86_64 -name ubuntu",
+                "ExecStop=/usr/bin/quickemu-rs --vm /vms/ubuntu.toml stop --pid $MAINPID --timeout 60",
+                "TimeoutStopSec=75",
+                "Restart=no",
+                "",
+                "[Install]",
+                "WantedBy=default.target",
+            ]
+        );
+        assert!(contents.ends_with('\n'));
+    }
+
+    #[test]
+    fn unit_contents_start_pre_and_scope() {
+        let service = ServiceCommands {
+            start_pre: Some("/usr/bin/swtpm socket".to_string()),
+            ..service()
+        };
+        let options = UnitOptions {
+            scope: UnitScope::System,
+            ..options()
+        };
+        let contents = unit_contents("ubuntu", Path::new("/vms/my vm"), &service, &Restart::default(), &options);
+        let lines = lines(&contents);
+        assert!(lines.contains(&"WorkingDirectory=\"/vms/my vm\""));
+        let start_pre = lines.iter().position(|line| *line == "ExecStartPre=/usr/bin/swtpm socket");
+        let start = lines.iter().position(|line| line.starts_with("ExecStart="));
+        assert!(start_pre.is_some() && start_pre < start);
+        assert!(lines.contains(&"WantedBy=multi-user.target"));
+    }
+
+    #[test]
+    fn restart_policy_mapping() {
+        let contents = |restart: Restart| unit_contents("ubuntu", Path::new("/vms/ubuntu"), &service(), &restart, &options());
+
+        let on_failure = contents(restart(RestartPolicy::OnFailure, Some(3)));
+        let on_failure = lines(&on_failure);
+        assert!(on_failure.contains(&"Restart=on-failure"));
+        assert!(on_failure.contains(&"RestartSec=5"));
+        assert!(on_failure.contains(&"StartLimitIntervalSec=infinity"));
+        // The first start counts towards systemd's limit, as well as each restart
+        assert!(on_failure.contains(&"StartLimitBurst=4"));
+        let service_start = on_failure.iter().position(|line| *line == "[Service]");
+        let burst = on_failure.iter().position(|line| line.starts_with("StartLimitBurst="));
+        assert!(burst < service_start);
+
+        let always = contents(restart(RestartPolicy::Always, None));
+        assert!(always.contains("Restart=always\n"));
+        assert!(!always.contains("StartLimitBurst="));
+
+        let saturated = contents(restart(RestartPolicy::Always, Some(u32::MAX)));
+        assert!(saturated.contains(&format!("StartLimitBurst={}\n", u32::MAX)));
+
+        let never = contents(restart(RestartPolicy::Never, Some(3)));
+        assert!(never.contains("Restart=no\n"));
+        assert!(!never.contains("RestartSec="));
+        assert!(!never.contains("StartLimitBurst="));
+    }
+
+    #[test]
+    fn stop_command_passes_overrides() {
+        let mut options = options();
+        options.overrides.push("network.monitor.type=none".parse().unwrap());
+        options.stop_timeout = Duration::from_secs(30);
+        assert_eq!(
+            stop_command(&options),
+            r#"/usr/bin/quickemu-rs --vm /vms/ubuntu.toml --set "network.monitor.type=\"none\"" stop --pid $MAINPID --timeout 30"#
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        let quoted = |arg: &str| quote(OsStr::new(arg));
+        assert_eq!(quoted("-name"), "-name");
+        assert_eq!(quoted("id=nic0,hostfwd=tcp::22220-:22"), "id=nic0,hostfwd=tcp::22220-:22");
+        assert_eq!(quoted(""), "\"\"");
+        assert_eq!(quoted("my vm"), "\"my vm\"");
+        assert_eq!(quoted("100%"), "100%%");
+        assert_eq!(quoted("$HOME"), "$$HOME");
+        assert_eq!(quoted("say \"hi\""), r#""say \"hi\"""#);
+        assert_eq!(quoted(r"C:\vms"), r#""C:\\vms""#);
+        assert_eq!(quoted("a;b"), "\"a;b\"");
+    }
+
+    #[test]
+    fn unit_names() {
+        assert_eq!(escape_unit_name("ubuntu"), "ubuntu");
+        assert_eq!(escape_unit_name("ubuntu-24.04"), r"ubuntu\x2d24.04");
+        assert_eq!(escape_unit_name(".hidden"), r"\x2ehidden");
+        assert_eq!(escape_unit_name("vms/ubuntu"), "vms-ubuntu");
+        assert_eq!(escape_unit_name("my vm"), r"my\x20vm");
+        assert_eq!(escape_unit_name("über"), r"\xc3\xbcber");
+    }
+}
@@ -2,7 +2,7 @@ use std::{
     borrow::Cow,
     ffi::OsStr,
     fmt,
-    net::{Ipv4Addr, SocketAddrV4, TcpListener},
+    net::{Ipv4Addr, SocketAddrV4, TcpListener, UdpSocket},
     thread::JoinHandle,
 };
 
@@ -11,7 +11,7 @@ use memfd_exec::Child;
 #[cfg(not(feature = "inbuilt_commands"))]
 use std::process::Child;
 
-use crate::error::Error;
+use crate::{data::Protocol, error::Error, plan::PlannedAction};
 
 #[derive(Debug)]
 pub struct ArgDisplay {
@@ -32,6 +32,9 @@ pub trait EmulatorArgs: Sized {
     fn launch_fns(self) -> impl IntoIterator<Item = LaunchFn> {
         std::iter::empty()
     }
+    fn planned_actions(&self) -> impl IntoIterator<Item = PlannedAction> {
+        std::iter::empty()
+    }
 }
 
 type LaunchFnReturnType = Result<Vec<LaunchFnReturn>, Error>;
@@ -77,10 +80,15 @@ pub(crate) fn plural_if(b: bool) -> &'static str {
 }
 
 pub(crate) fn find_port(port: u16, offset: u16) -> Option<u16> {
-    (port..=port + offset).find(|port| {
-        let port = SocketAddrV4::new(Ipv4Addr::LOCALHOST, *port);
-        TcpListener::bind(port).is_ok()
-    })
+    (port..=port + offset).find(|port| port_available(Protocol::Tcp, Ipv4Addr::LOCALHOST, *port))
+}
+
+pub(crate) fn port_available(protocol: Protocol, addr: Ipv4Addr, port: u16) -> bool {
+    let addr = SocketAddrV4::new(addr, port);
+    match protocol {
+        Protocol::Tcp => TcpListener::bind(addr).is_ok(),
+        Protocol::Udp => UdpSocket::bind(addr).is_ok(),
+    }
 }
 
 #[macro_export]
@@ -124,12 +132,14 @@ macro_rules! full_qemu_args {
             let mut qemu_args: Vec<QemuArg> = Vec::new();
             let mut before_launch_fns = Vec::new();
             let mut after_launch_fns = Vec::new();
+            let mut planned_actions = Vec::new();
             $(
                 {
                     let (args, warn) = $arg?;
                     warnings.extend(warn);
                     display.extend(args.display());
                     qemu_args.extend(args.qemu_args());
+                    planned_actions.extend(args.planned_actions());
 
                     for launch_fn in args.launch_fns() {
                         match launch_fn {
@@ -146,6 +156,7 @@ macro_rules! full_qemu_args {
                 display,
                 before_launch_fns,
                 after_launch_fns,
+                planned_actions,
             })
         }
     };
@@ -0,0 +1,374 @@
+use std::{collections::HashSet, ops::Range, path::Path};
+
+use serde::Serialize;
+use which::which;
+
+use crate::{
+    config::Config,
+    data::{Display, MonitorArg, MonitorInner},
+    error::{ConfigError, Error, Warning},
+    fl,
+    overrides::Overrides,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+// 1-based line and column, as expected by editors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // Dotted TOML key path, e.g. `images.iso[0].path`
+    pub key: Option<String>,
+    // Only known when the configuration was checked from its source
+    pub span: Option<Span>,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    fn error(key: impl Into<String>, error: &Error) -> Self {
+        Self {
+            severity: Severity::Error,
+            key: Some(key.into()),
+            span: None,
+            message: error.to_string(),
+            suggestion: error_suggestion(error),
+        }
+    }
+    fn warning(key: impl Into<String>, warning: &Warning) -> Self {
+        Self {
+            severity: Severity::Warning,
+            key: Some(key.into()),
+            span: None,
+            message: warning.to_string(),
+            suggestion: warning_suggestion(warning),
+        }
+    }
+}
+
+impl Config {
+    // Parse and validate a configuration file, locating each diagnostic within it
+    pub fn check(file: &Path) -> Result<Vec<Diagnostic>, ConfigError> {
+        Self::check_with_overrides(file, &Overrides::default())
+    }
+
+    pub fn check_with_overrides(file: &Path, overrides: &Overrides) -> Result<Vec<Diagnostic>, ConfigError> {
+        let contents = std::fs::read_to_string(file)?;
+        let document = toml_edit::ImDocument::parse(contents.as_str()).ok();
+        let mut diagnostics = match Self::from_toml(&contents, file, overrides) {
+            Ok(config) => config.validate(),
+            Err(ConfigError::Parse(err)) => {
+                return Ok(vec![Diagnostic {
+                    severity: Severity::Error,
+                    key: None,
+                    span: err.span().map(|span| to_span(&contents, span)),
+                    message: err.message().to_string(),
+                    suggestion: None,
+                }])
+            }
+            // Located below through its key, as the error's own location is within the merged table
+            Err(ConfigError::ParseMerged(key, err)) => vec![Diagnostic {
+                severity: Severity::Error,
+                key,
+                span: None,
+                message: err.message().to_string(),
+                suggestion: None,
+            }],
+            Err(err @ (ConfigError::UnsupportedVersion(_) | ConfigError::Include(..) | ConfigError::IncludeCycle(_) | ConfigError::InvalidOverride(_))) => vec![Diagnostic {
+                severity: Severity::Error,
+                key: match &err {
+                    ConfigError::UnsupportedVersion(_) => Some("version".to_string()),
+                    ConfigError::InvalidOverride(key) => Some(key.clone()),
+                    _ => Some("include".to_string()),
+                },
+                span: None,
+                message: err.to_string(),
+                suggestion: None,
+            }],
+            Err(err) => return Err(err),
+        };
+        for diagnostic in &mut diagnostics {
+            diagnostic.span = document
+                .as_ref()
+                .zip(diagnostic.key.as_deref())
+                .and_then(|(document, key)| locate(document.as_item(), key))
+                .map(|span| to_span(&contents, span));
+        }
+        Ok(diagnostics)
+    }
+
+    // Collect every problem which would prevent the VM from launching, without launching it
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let vm_dir = match &self.vm_dir {
+            Some(vm_dir) => vm_dir.clone(),
+            None => std::env::current_dir().unwrap_or_default().join(&self.vm_name),
+        };
+        let vm_dir = vm_dir.as_path();
+        let mut diagnostics = Vec::new();
+
+        let qemu_bin = self.machine.arch.qemu_binary();
+        if which(qemu_bin).is_err() {
+            diagnostics.push(Diagnostic::error("machine.arch", &Error::QemuNotFound(qemu_bin)));
+        }
+        #[cfg(target_arch = "x86_64")]
+        if let Err(err) = self.guest.validate_cpu() {
+            diagnostics.push(Diagnostic::error("guest", &err));
+        }
+
+        match self.machine.cpu_args(self.guest) {
+            Ok((_, warnings)) => diagnostics.extend(
+                warnings
+                    .iter()
+                    .map(|warning| Diagnostic::warning("machine.cpu_threads", warning)),
+            ),
+            Err(err) => diagnostics.push(Diagnostic::error("machine.cpu_threads", &err)),
+        }
+        match self.machine.ram_args(self.guest) {
+            Ok((_, warning)) => diagnostics.extend(warning.iter().map(|warning| Diagnostic::warning("machine.ram", warning))),
+            Err(err) => diagnostics.push(Diagnostic::error("machine.ram", &err)),
+        }
+        if self.machine.tpm {
+            if let Err(err) = which("swtpm") {
+                diagnostics.push(Diagnostic::error("machine.tpm", &Error::from(err)));
+            }
+        }
+        if let Err(err) = self.machine.boot_args(vm_dir, self.guest, true) {
+            diagnostics.push(Diagnostic::error("machine.boot", &err));
+        }
+
+        let usb_controller = self.io.usb_controller.unwrap_or(self.guest.default_usb_controller());
+        let soundcard = self.io.soundcard.unwrap_or(self.guest.default_soundcard());
+        if let Err(err) = soundcard.validate(usb_controller) {
+            diagnostics.push(Diagnostic::error("io.soundcard", &err));
+        }
+        match self.io.display.audio(soundcard) {
+            Ok((_, warning)) => diagnostics.extend(warning.iter().map(|warning| Diagnostic::warning("io.soundcard", warning))),
+            Err(err) => diagnostics.push(Diagnostic::error("io.soundcard", &err)),
+        }
+        diagnostics.extend(display_diagnostics(&self.io.display));
+
+        for (index, interface) in self.network.interfaces.iter().enumerate() {
+            if let Err((key, err)) = interface.validate() {
+                diagnostics.push(Diagnostic::error(format!("network.interfaces[{index}].{key}"), &err));
+            }
+        }
+        if let Err(err) = self.network.validate_port_forwards() {
+            diagnostics.push(Diagnostic::error("network.interfaces", &err));
+        }
+        diagnostics.extend(monitor_diagnostics("network.monitor", &self.network.monitor));
+        diagnostics.extend(monitor_diagnostics("network.serial", &self.network.serial));
+
+        // Missing disks are created on launch. Other images must exist unless they're no longer mounted after installation
+        let installed = match self.images.disk_args(self.guest, vm_dir, false, &mut HashSet::new(), true) {
+            Ok(disks) => disks.installed(),
+            Err(err) => {
+                let key = if matches!(err, Error::MacBootloader) { "guest" } else { "images.disk" };
+                diagnostics.push(Diagnostic::error(key, &err));
+                false
+            }
+        };
+        for (kind, images) in [("iso", &self.images.iso), ("img", &self.images.img)] {
+            for (index, image) in images.iter().enumerate().filter(|(_, image)| image.always_mount || !installed) {
+                let path = vm_dir.join(&image.path);
+                if !path.exists() {
+                    diagnostics.push(Diagnostic::error(
+                        format!("images.{kind}[{index}].path"),
+                        &Error::NonexistentImage(path.display().to_string()),
+                    ));
+                }
+            }
+        }
+
+        diagnostics.sort_by_key(|diagnostic| std::cmp::Reverse(diagnostic.severity));
+        diagnostics
+    }
+}
+
+fn display_diagnostics(display: &Display) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    #[cfg(not(target_os = "macos"))]
+    if let crate::data::DisplayType::Spice { viewer, spice_port, .. } = display.display_type {
+        if crate::utils::find_port(spice_port, 9).is_none() {
+            diagnostics.push(Diagnostic::error("io.display.spice_port", &Error::UnavailablePort(spice_port)));
+        }
+        let viewer_bin = match viewer {
+            crate::data::Viewer::Spicy => Some("spicy"),
+            crate::data::Viewer::Remote => Some("remote-viewer"),
+            crate::data::Viewer::None => None,
+        };
+        if let Some(viewer_bin) = viewer_bin.filter(|bin| which(bin).is_err()) {
+            let err = Error::ViewerNotFound(viewer_bin);
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                ..Diagnostic::error("io.display.viewer", &err)
+            });
+        }
+    }
+    diagnostics
+}
+
+fn monitor_diagnostics<T: MonitorArg + Clone>(key: &str, monitor: &MonitorInner<T>) -> Option<Diagnostic> {
+    let mut monitor = monitor.clone();
+    monitor.validate().err().map(|err| Diagnostic::error(key, &err))
+}
+
+fn error_suggestion(error: &Error) -> Option<String> {
+    Some(match error {
+        Error::QemuNotFound(_) => fl!("suggest-install-qemu"),
+        #[cfg(not(feature = "inbuilt_commands"))]
+        Error::Which(_) => fl!("suggest-install-swtpm"),
+        Error::LegacyBoot | Error::UnsupportedBootCombination => fl!("suggest-efi-boot"),
+        Error::Ovmf => fl!("suggest-install-ovmf"),
+        Error::Riscv64Bootloader => fl!("suggest-riscv64-bootloader"),
+        Error::MacBootloader => fl!("suggest-mac-bootloader"),
+        Error::ConflictingSoundUsb => fl!("suggest-xhci"),
+        Error::UnavailablePort(_) | Error::PortForwardConflict(..) => fl!("suggest-other-port"),
+        Error::UnsupportedQmp(_) => fl!("suggest-qmp-monitor"),
+        Error::NonexistentImage(_) => fl!("suggest-image-path"),
+        Error::InsufficientRam(..) => fl!("suggest-set-ram"),
+        Error::ViewerNotFound(_) => fl!("suggest-viewer"),
+        Error::MissingBridge(_) | Error::MissingTap(_) => fl!("suggest-network-setup"),
+        Error::InvalidNetworkName(_) => fl!("suggest-network-name"),
+        Error::NatAddress(..) => fl!("suggest-nat-address"),
+        _ => return None,
+    })
+}
+
+fn warning_suggestion(warning: &Warning) -> Option<String> {
+    match warning {
+        Warning::MacOSCorePow2(recommended) => Some(fl!("suggest-cpu-threads", recommended = recommended)),
+        Warning::InsufficientRamConfiguration(..) => Some(fl!("suggest-set-ram")),
+        _ => None,
+    }
+}
+
+// Find the span of the value at a key path, falling back to its closest parent present in the file
+fn locate(mut item: &toml_edit::Item, key: &str) -> Option<Range<usize>> {
+    let mut span = None;
+    for part in key.split('.') {
+        let (name, index) = match part.split_once('[') {
+            Some((name, index)) => (name, index.trim_end_matches(']').parse::<usize>().ok()),
+            None => (part, None),
+        };
+        let Some(next) = item.get(name) else { break };
+        item = next;
+        span = item.span().or(span);
+        if let Some(index) = index {
+            let Some(next) = item.get(index) else { break };
+            item = next;
+            span = item.span().or(span);
+        }
+    }
+    span
+}
+
+// The dotted key path of the innermost key or value containing the span, in the form `locate` accepts
+pub(crate) fn key_at(item: &toml_edit::Item, span: &Range<usize>) -> Option<String> {
+    let contains = |range: Option<Range<usize>>| range.is_some_and(|range| range.start <= span.start && span.end <= range.end);
+    let keys: Vec<&toml_edit::Key> = match item {
+        toml_edit::Item::Table(table) => table.iter().filter_map(|(key, _)| table.key(key)).collect(),
+        toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => table.iter().filter_map(|(key, _)| table.key(key)).collect(),
+        _ => Vec::new(),
+    };
+    for key in keys {
+        let name = key.get();
+        let Some(child) = item.get(name) else { continue };
+        let len = match child {
+            toml_edit::Item::ArrayOfTables(tables) => tables.len(),
+            toml_edit::Item::Value(toml_edit::Value::Array(array)) => array.len(),
+            _ => 0,
+        };
+        for (index, element) in (0..len).filter_map(|index| Some((index, child.get(index)?))) {
+            if let Some(inner) = key_at(element, span) {
+                return Some(format!("{name}[{index}].{inner}"));
+            }
+            if contains(element.span()) {
+                return Some(format!("{name}[{index}]"));
+            }
+        }
+        if let Some(inner) = key_at(child, span) {
+            return Some(format!("{name}.{inner}"));
+        }
+        if contains(child.span()) || contains(key.span()) {
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+fn to_span(contents: &str, range: Range<usize>) -> Span {
+    Span {
+        start: to_position(contents, range.start),
+        end: to_position(contents, range.end),
+    }
+}
+
+fn to_position(contents: &str, offset: usize) -> Position {
+    let before = &contents[..offset.min(contents.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Configurations without a version are migrated, and so are parsed from a merged table rather than the file itself
+    #[test]
+    fn check_locates_type_errors_in_migrated_configs() {
+        let dir = std::env::temp_dir().join(format!("quickemu-check-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("vm.toml");
+        std::fs::write(&file, "guest = { os = \"linux\" }\n\n[machine]\ntpm = \"yes\"\n").unwrap();
+
+        let diagnostics = Config::check(&file);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let diagnostics = diagnostics.unwrap();
+        let [diagnostic] = diagnostics.as_slice() else {
+            panic!("Expected a single diagnostic, found {diagnostics:?}");
+        };
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.key.as_deref(), Some("machine.tpm"));
+        assert_eq!(
+            diagnostic.span,
+            Some(Span {
+                start: Position { line: 4, column: 7 },
+                end: Position { line: 4, column: 12 },
+            })
+        );
+    }
+
+    #[test]
+    fn key_at_finds_nested_keys() {
+        let contents = "guest = { os = \"linux\" }\n\n[[network.interfaces]]\ntype = \"nat\"\nport_forwards = [{ host = 80, guest = 8080 }]\n";
+        let document = toml_edit::ImDocument::parse(contents).unwrap();
+        let offset = contents.find("8080").unwrap();
+        assert_eq!(
+            key_at(document.as_item(), &(offset..offset + 4)).as_deref(),
+            Some("network.interfaces[0].port_forwards[0].guest")
+        );
+        let offset = contents.find("linux").unwrap();
+        assert_eq!(key_at(document.as_item(), &(offset..offset + 5)).as_deref(), Some("guest.os"));
+    }
+}